@@ -0,0 +1,5 @@
+// sqlx::migrate!() embeds the migrations at compile time; make sure new or
+// edited migration files trigger a rebuild.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE notes ADD COLUMN deleted_at TIMESTAMP NULL DEFAULT NULL;
\ No newline at end of file
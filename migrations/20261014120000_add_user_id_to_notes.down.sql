@@ -0,0 +1,3 @@
+ALTER TABLE notes DROP FOREIGN KEY fk_notes_user;
+ALTER TABLE notes DROP INDEX uq_notes_user_title, ADD UNIQUE KEY title (title);
+ALTER TABLE notes DROP COLUMN user_id;
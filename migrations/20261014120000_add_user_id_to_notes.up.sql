@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- Notes created before this migration have no owner and are not visible to anyone
+ALTER TABLE notes ADD COLUMN user_id CHAR(36) NULL DEFAULT NULL;
+ALTER TABLE notes ADD CONSTRAINT fk_notes_user FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+-- Titles only need to be unique per user
+ALTER TABLE notes DROP INDEX title, ADD UNIQUE KEY uq_notes_user_title (user_id, title);
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE users RENAME COLUMN email TO username;
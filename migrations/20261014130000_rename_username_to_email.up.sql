@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE users RENAME COLUMN username TO email;
\ No newline at end of file
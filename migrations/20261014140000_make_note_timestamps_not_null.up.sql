@@ -0,0 +1,6 @@
+-- Add up migration script here
+UPDATE notes SET created_at = CURRENT_TIMESTAMP WHERE created_at IS NULL;
+UPDATE notes SET updated_at = created_at WHERE updated_at IS NULL;
+ALTER TABLE notes
+    MODIFY created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    MODIFY updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP;
\ No newline at end of file
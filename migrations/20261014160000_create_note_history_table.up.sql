@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- One row per create, edit, delete and restore of a note, previous_* hold the
+-- note as it was before the change and are NULL for a create. Soft deletes
+-- keep the note row, so its history stays too; the rows only go when the
+-- note itself is removed.
+CREATE TABLE IF NOT EXISTS note_history (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    note_id CHAR(36) NOT NULL,
+    user_id CHAR(36) NOT NULL,
+    action VARCHAR(16) NOT NULL,
+    previous_title VARCHAR(255) NULL DEFAULT NULL,
+    previous_content TEXT NULL DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    KEY idx_note_history_note (note_id, id),
+    FOREIGN KEY (note_id) REFERENCES notes (id) ON DELETE CASCADE
+);
\ No newline at end of file
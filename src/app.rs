@@ -0,0 +1,331 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{
+        header::{ACCEPT, VARY},
+        HeaderMap, Method, StatusCode, Uri,
+    },
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    SeedableRng,
+};
+use serde::{Deserialize, Serialize};
+use tower::ServiceBuilder;
+use tower_http::{
+    compression::{
+        predicate::{DefaultPredicate, Predicate, SizeAbove},
+        CompressionLayer,
+    },
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+
+use crate::{
+    body_limit, cors, docs, error::AppError, greetings, lookup, method_not_allowed, numbers, poems,
+    rate_limit, routes::route::create_router, static_files::static_files, telemetry, timeout,
+    AppState,
+};
+
+// Smaller bodies are sent as is, compressing them costs more than it saves
+const COMPRESSION_MIN_BYTES: u16 = 1024;
+
+// Every route and middleware of the server, built from nothing but the
+// state. main serves it, the tests under tests/ drive it in-process with
+// tower's oneshot on a state of their own, and another binary can mount it
+// under a prefix with `Router::nest`.
+pub fn build_app(app_state: Arc<AppState>) -> Router {
+    let app = Router::new()
+        .route("/", get(hello_world).post(post_hello_world))
+        // Every greeting lands in the history, see greetings::record_greeting
+        .route(
+            "/greet/:name",
+            get(greet_path).with_state(app_state.clone()),
+        )
+        .route(
+            "/greet",
+            get(greet_query)
+                .post(greet_body)
+                .with_state(app_state.clone()),
+        )
+        // Static segments win over `:name`, so this path can't greet "strict"
+        .route(
+            "/greet/strict",
+            get(greet_query_strict).with_state(app_state.clone()),
+        )
+        .merge(greetings::greetings(app_state.clone()))
+        .route(
+            "/random",
+            get(return_something_random).with_state(app_state.config.random_seed),
+        )
+        .merge(lookup::lookup(app_state.clone()))
+        .merge(numbers::numbers_management(app_state.clone()))
+        // Let's add additional routes. Note that we can structure complex
+        // routing hierarchies using methods like merge and nest.
+        .merge(pingpong())
+        .nest("/kingkong", kingkong())
+        .merge(poems::poems(app_state.clone()))
+        .merge(static_files(&app_state.config.static_dir))
+        .merge(create_router(app_state.clone()))
+        .merge(docs::docs_router())
+        .merge(telemetry::metrics_router(app_state.clone()))
+        // Anything no route above matched
+        .fallback(handler_404)
+        // Layers wrap every route above, including the merged and nested ones.
+        // Extractors stop reading a body once it passes MAX_BODY_BYTES and
+        // answer 413. A route can raise its own limit by adding another
+        // DefaultBodyLimit layer, the innermost one wins.
+        .layer(DefaultBodyLimit::max(app_state.config.max_body_bytes))
+        .layer(middleware::from_fn_with_state(
+            app_state.config.max_body_bytes,
+            body_limit::json_payload_too_large,
+        ))
+        .layer(middleware::from_fn(
+            method_not_allowed::json_method_not_allowed,
+        ))
+        // Compression only applies when the client sends Accept-Encoding.
+        .layer(
+            CompressionLayer::new()
+                .compress_when(DefaultPredicate::new().and(SizeAbove::new(COMPRESSION_MIN_BYTES))),
+        )
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            rate_limit::rate_limit,
+        ))
+        // Bounds everything above, the Redis call of the rate limiter
+        // included. It stays inside track_metrics, which must see the 504
+        // rather than be dropped along with the timed out future.
+        .layer(middleware::from_fn_with_state(
+            app_state.config.request_timeout,
+            timeout::request_timeout,
+        ))
+        .layer(middleware::from_fn(telemetry::track_metrics));
+    // Outside the rate limiter so preflight requests are answered first
+    let app = match cors::cors_layer(&app_state.config) {
+        Some(cors) => app
+            .layer(cors)
+            .layer(middleware::from_fn(cors::preflight_no_content)),
+        None => app,
+    };
+    // Outermost, so every response carries X-Request-Id, preflights and
+    // errors from any merged router included. A client supplied id is kept,
+    // otherwise a UUID v4 is generated. Handlers can read it as
+    // `Extension<RequestId>`, and the request span records it before
+    // anything else logs. access_log replaces the TraceLayer's own response
+    // event, so every request is logged exactly once.
+    app.layer(
+        ServiceBuilder::new()
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(telemetry::make_request_span)
+                    .on_response(()),
+            )
+            .layer(middleware::from_fn(telemetry::access_log))
+            .layer(PropagateRequestIdLayer::x_request_id()),
+    )
+}
+
+// JSON 404 for unknown paths, API clients expect a body they can parse.
+// Only requests that no route matched get here, so /poem and the other HTML
+// routes keep their own responses, including their own 404s.
+async fn handler_404(method: Method, uri: Uri) -> impl IntoResponse {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({
+            "error": "not found",
+            "method": method.as_str(),
+            "path": uri.path(),
+        })),
+    )
+}
+
+async fn hello_world() -> &'static str {
+    "Hello, World1!"
+}
+
+async fn post_hello_world() -> &'static str {
+    "Hello, World1 post!"
+}
+
+// Two functions that return a router. This is very useful in larger applications
+// with lots of routes.
+fn pingpong() -> Router {
+    Router::new().route("/ping", get(|| async { "pong" }))
+}
+
+fn kingkong() -> Router {
+    async fn king() -> &'static str {
+        "Kong"
+    }
+    Router::new().route("/king", get(king))
+}
+
+// Path is an "Extractor". Extractors are used to extract data from the request.
+// .route("/greet/:name", get(greet_path))
+async fn greet_path(State(state): State<Arc<AppState>>, Path(name): Path<String>) -> String {
+    greetings::record_greeting(&state, "Hello", &name);
+    format!("Hello, {}!", name)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct QueryParameters {
+    salutation: Option<String>,
+    name: Option<String>,
+}
+
+// Query is also an "Extractor".
+// The greeting is JSON `{ "message": ... }` when the Accept header prefers
+// it, plain text otherwise.
+// .route("/greet", get(greet_query))
+async fn greet_query(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<QueryParameters>,
+) -> Response {
+    let salutation = params.salutation.unwrap_or_else(|| "Hello".to_string());
+    let name = params.name.unwrap_or_else(|| "World".to_string());
+    greetings::record_greeting(&state, &salutation, &name);
+    let message = format!("{}, {}!", salutation, name);
+    // Caches must not hand the text version to a JSON client
+    let vary = [(VARY, "accept")];
+    match prefers_json(&headers) {
+        true => (vary, Json(serde_json::json!({ "message": message }))).into_response(),
+        false => (vary, message).into_response(),
+    }
+}
+
+// Picks between JSON and text (plain or HTML) by the q values in Accept,
+// e.g. `text/plain;q=0.5, application/json` is JSON. A missing header, `*/*`
+// and ties go to text, which is what curl and browsers show best.
+pub(crate) fn prefers_json(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(ACCEPT).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let mut best = (false, 0.0);
+    for range in accept.split(',') {
+        let mut parts = range.split(';').map(str::trim);
+        let json = match parts.next().unwrap_or_default() {
+            "application/json" | "application/*" => true,
+            "text/plain" | "text/html" | "text/*" | "*/*" => false,
+            _ => continue,
+        };
+        let q = parts
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if q > best.1 {
+            best = (json, q);
+        }
+    }
+    best.0
+}
+
+// Query parameters greet_query understands, anything else is a typo
+const GREET_PARAMETERS: [&str; 2] = ["salutation", "name"];
+
+// Like greet_query, but `?nam=Bob` is a 400 listing every unknown key
+// rather than a silent "Hello, World!".
+// .route("/greet/strict", get(greet_query_strict))
+async fn greet_query_strict(
+    state: State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<Response, AppError> {
+    let mut unknown = Vec::new();
+    for (key, _) in &pairs {
+        if !GREET_PARAMETERS.contains(&key.as_str()) && !unknown.contains(key) {
+            unknown.push(key.clone());
+        }
+    }
+    if !unknown.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "Unknown query parameters: {}, expected {}",
+            unknown.join(", "),
+            GREET_PARAMETERS.join(" or ")
+        )));
+    }
+
+    let mut params = QueryParameters::default();
+    for (key, value) in pairs {
+        match key.as_str() {
+            "salutation" => params.salutation = Some(value),
+            _ => params.name = Some(value),
+        }
+    }
+    Ok(greet_query(state, headers, Query(params)).await)
+}
+
+// Json is the third commonly used extractor.
+// .route("/greet", get(greet_query).post(greet_body))
+async fn greet_body(
+    State(state): State<Arc<AppState>>,
+    Json(params): Json<QueryParameters>,
+) -> String {
+    let salutation = params.salutation.unwrap_or_else(|| "Hello".to_string());
+    let name = params.name.unwrap_or_else(|| "World".to_string());
+    greetings::record_greeting(&state, &salutation, &name);
+    format!("{}, {}!", salutation, name)
+}
+
+// NOTE: You can learn all details about extractor at
+// https://docs.rs/axum/latest/axum/extract/index.html#defining-custom-extractors
+
+// Both optional. `seed` makes the pick reproducible, the same seed always
+// gives the same response, and RANDOM_SEED is used when it is missing.
+// With the default weights seed 2 gives the text, 4 the 501 and 0 the JSON
+// (pinned by the tests, StdRng may change between rand versions).
+// `weights` is three comma separated numbers for the text, 501 and JSON
+// outcomes, e.g. `weights=0,1,0` always fails.
+#[derive(Deserialize, Debug, Default)]
+struct RandomParameters {
+    seed: Option<u64>,
+    weights: Option<String>,
+}
+
+// Used when no weights are given, every outcome equally likely
+const RANDOM_WEIGHTS: [f64; 3] = [1.0, 1.0, 1.0];
+
+// The easiest way to return different data types from a handler
+// is to convert them into Response, which implements IntoRespose.
+// .route("/random", get(return_something_random))
+async fn return_something_random(
+    State(default_seed): State<Option<u64>>,
+    Query(params): Query<RandomParameters>,
+) -> Result<Response, AppError> {
+    let weights = match &params.weights {
+        Some(weights) => parse_weights(weights)?,
+        None => RANDOM_WEIGHTS,
+    };
+    let outcomes = WeightedIndex::new(weights)
+        .map_err(|err| AppError::BadRequest(format!("Invalid weights: {}", err)))?;
+    let outcome = match params.seed.or(default_seed) {
+        Some(seed) => outcomes.sample(&mut StdRng::seed_from_u64(seed)),
+        None => outcomes.sample(&mut rand::thread_rng()),
+    };
+    Ok(match outcome {
+        0 => "Hello, World!".into_response(),
+        1 => StatusCode::NOT_IMPLEMENTED.into_response(),
+        _ => Json(42).into_response(),
+    })
+}
+
+fn parse_weights(weights: &str) -> Result<[f64; 3], AppError> {
+    let invalid = || {
+        AppError::BadRequest(format!(
+            "Invalid weights {:?}, expected three comma separated numbers",
+            weights
+        ))
+    };
+    let parsed = weights
+        .split(',')
+        .map(|weight| weight.trim().parse::<f64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    parsed.try_into().map_err(|_| invalid())
+}
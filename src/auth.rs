@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use axum::{
+    extract::{Extension, Query, Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::Response,
+};
+use jsonwebtoken::{
+    decode, encode, errors::ErrorKind, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::AppError, AppState};
+
+// Lifetime of tokens issued by the login endpoint.
+pub const TOKEN_TTL_SECS: i64 = 60 * 60;
+
+// JWT payload. `sub` is the user id, `exp` the expiry as a unix timestamp,
+// `admin` is copied from users.is_admin at login.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: usize,
+    // Missing in tokens issued before admins existed
+    #[serde(default)]
+    pub admin: bool,
+}
+
+// Middleware for routes that need a logged in user. Validates the bearer
+// token and makes its Claims available to handlers via Extension<Claims>.
+// .route_layer(middleware::from_fn_with_state(app_state.clone(), require_auth))
+pub async fn require_auth(
+    State(data): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let token = bearer_token(&req).ok_or_else(missing_token)?;
+
+    let claims = decode_token(token, &data.config.jwt_secret)?;
+    req.extensions_mut().insert(claims);
+
+    Ok(next.run(req).await)
+}
+
+#[derive(Deserialize)]
+struct SocketToken {
+    access_token: Option<String>,
+}
+
+// require_auth for the WebSocket routes. Browsers can't set headers on an
+// upgrade request, so the token may also come as `?access_token=`. Only
+// the path is logged, the query string never is.
+// .route_layer(middleware::from_fn_with_state(app_state.clone(), require_socket_auth))
+pub async fn require_socket_auth(
+    State(data): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let token = match bearer_token(&req) {
+        Some(token) => token.to_string(),
+        None => Query::<SocketToken>::try_from_uri(req.uri())
+            .ok()
+            .and_then(|Query(query)| query.access_token)
+            .ok_or_else(missing_token)?,
+    };
+
+    let claims = decode_token(&token, &data.config.jwt_secret)?;
+    req.extensions_mut().insert(claims);
+
+    Ok(next.run(req).await)
+}
+
+fn bearer_token(req: &Request) -> Option<&str> {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+fn missing_token() -> AppError {
+    AppError::Unauthorized("Missing bearer token".to_string())
+}
+
+// Route layer for admin-only routes. Goes inside require_auth, which
+// provides the Claims, and answers 403 for everyone else.
+// .route_layer(middleware::from_fn(require_admin))
+pub async fn require_admin(
+    Extension(claims): Extension<Claims>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !claims.admin {
+        return Err(AppError::Forbidden("Admin privileges required".to_string()));
+    }
+    Ok(next.run(req).await)
+}
+
+fn decode_token(token: &str, secret: &str) -> Result<Claims, AppError> {
+    decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map(|data| data.claims)
+    .map_err(|err| match err.kind() {
+        ErrorKind::ExpiredSignature => AppError::Unauthorized("Token has expired".to_string()),
+        _ => AppError::Unauthorized("Invalid token".to_string()),
+    })
+}
+
+// Sign a token for the given user id. Returns the token and its expiry as
+// a unix timestamp.
+pub fn issue_token(user_id: &str, admin: bool, secret: &str) -> Result<(String, usize), AppError> {
+    let exp = (chrono::Utc::now().timestamp() + TOKEN_TTL_SECS) as usize;
+    let claims = Claims {
+        sub: user_id.to_string(),
+        exp,
+        admin,
+    };
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )?;
+    Ok((token, exp))
+}
+
+// Verified in place of a stored hash when a login names an unknown email, so
+// the request costs the same argon2 work as a wrong password. Made by
+// hash_password, so it has the same parameters. The outcome is ignored.
+pub const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$C3tUtGzXqyM+jOfA2xxtEA$pV7RAZbl6/BgvdxDjJgJseqFUrFCo44BoPdg9yOswEY";
+
+// Check a password against a stored argon2 PHC string. An unparsable hash
+// counts as a mismatch.
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+// Hash a new password into an argon2 PHC string with a random salt.
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default().hash_password(password.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A hash that fails to parse would skip argon2 and answer fast
+    #[test]
+    fn the_dummy_hash_costs_as_much_as_a_real_one() {
+        let dummy = PasswordHash::new(DUMMY_PASSWORD_HASH).unwrap();
+        let real = hash_password("password").unwrap();
+        let real = PasswordHash::new(&real).unwrap();
+        assert_eq!(dummy.algorithm, real.algorithm);
+        assert_eq!(dummy.params, real.params);
+        assert!(!verify_password("password", DUMMY_PASSWORD_HASH));
+    }
+}
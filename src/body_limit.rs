@@ -0,0 +1,28 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::error::AppError;
+
+// axum's extractors reject an oversized body with a plain text 413. Swap that
+// for the usual JSON error body. Responses that are already JSON are left
+// alone.
+pub async fn json_payload_too_large(
+    State(limit): State<usize>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(req).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE && !is_json {
+        return AppError::PayloadTooLarge(limit).into_response();
+    }
+    response
+}
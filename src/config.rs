@@ -0,0 +1,274 @@
+use axum::http::HeaderValue;
+use std::{
+    env, fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+// Settings read once from the environment at startup.
+#[derive(Clone)]
+pub struct Config {
+    pub database_url: String,
+    // Read-only handlers use it when set, see AppState::db_read
+    pub database_replica_url: Option<String>,
+    pub db_max_connections: u32,
+    // Extra attempts at startup for MySQL and for Redis each
+    pub connect_retries: u32,
+    pub db_acquire_timeout: Duration,
+    pub db_idle_timeout: Duration,
+    pub db_max_lifetime: Duration,
+    pub redis_url: String,
+    pub bind_addr: SocketAddr,
+    pub jwt_secret: String,
+    pub redis_max_connections: usize,
+    pub rate_limit_per_minute: u64,
+    // Take client IPs from X-Forwarded-For / X-Real-IP, see ClientIp
+    pub trust_proxy: bool,
+    pub shutdown_timeout: Duration,
+    pub note_cache_ttl: Duration,
+    // None (unset) allows any origin, empty turns CORS off, `*` is any origin
+    pub cors_allowed_origins: Option<Vec<HeaderValue>>,
+    pub cors_allow_credentials: bool,
+    pub max_body_bytes: usize,
+    // Largest CSV file POST /api/notes/import accepts
+    pub max_import_bytes: usize,
+    // Most notes one POST /api/notes/batch may create
+    pub max_batch: usize,
+    pub request_timeout: Duration,
+    pub slow_query_threshold: Duration,
+    pub poems_dir: PathBuf,
+    pub poem_cache_ttl: Duration,
+    pub templates_dir: PathBuf,
+    pub static_dir: PathBuf,
+    // Seed GET /random uses when the request has none, unset is random
+    pub random_seed: Option<u64>,
+}
+
+// Every problem found while loading the config, so they can all be fixed
+// in one go instead of one restart per variable.
+#[derive(Debug, Default)]
+pub struct ConfigError {
+    missing: Vec<&'static str>,
+    invalid: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut problems = Vec::new();
+        if !self.missing.is_empty() {
+            problems.push(format!(
+                "missing required environment variables: {}",
+                self.missing.join(", ")
+            ));
+        }
+        problems.extend(self.invalid.iter().cloned());
+        write!(f, "{}", problems.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let mut errors = ConfigError::default();
+
+        let database_url = errors.url("DATABASE_URL", &["mysql", "mariadb"]);
+        let database_replica_url = is_set("DATABASE_REPLICA_URL")
+            .then(|| errors.url("DATABASE_REPLICA_URL", &["mysql", "mariadb"]));
+        let db_max_connections = errors.parsed("DB_MAX_CONNECTIONS", 10);
+        let connect_retries = errors.parsed("DB_CONNECT_RETRIES", 4);
+        // Same default as the MySQL pool size
+        let redis_max_connections = errors.parsed("REDIS_MAX_CONNECTIONS", 10);
+        // An empty pool would make every request wait for a connection
+        // until the acquire timeout
+        if db_max_connections == 0 || redis_max_connections == 0 {
+            errors.invalid.push(
+                "DB_MAX_CONNECTIONS and REDIS_MAX_CONNECTIONS must be at least 1".to_string(),
+            );
+        }
+        // How long a query waits for a free connection, also bounds each
+        // connect attempt at startup
+        let db_acquire_timeout_secs = errors.parsed("DB_ACQUIRE_TIMEOUT_SECS", 10);
+        let db_idle_timeout_secs = errors.parsed("DB_IDLE_TIMEOUT_SECS", 600);
+        let db_max_lifetime_secs = errors.parsed("DB_MAX_LIFETIME_SECS", 1800);
+        let redis_url = errors.url("REDIS_URL", &["redis", "rediss", "redis+unix", "unix"]);
+        let jwt_secret = errors.required("JWT_SECRET");
+        let host = errors.parsed("HOST", IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        let port = errors.parsed("PORT", 3000u16);
+        // A full address wins over HOST/PORT when both are given
+        let bind_addr = errors.parsed("BIND_ADDR", SocketAddr::new(host, port));
+        let rate_limit_per_minute = errors.parsed("RATE_LIMIT_PER_MINUTE", 60);
+        let trust_proxy = errors.parsed("TRUST_PROXY", false);
+        let shutdown_timeout_secs = errors.parsed("SHUTDOWN_TIMEOUT_SECS", 30);
+        let note_cache_ttl_secs = errors.parsed("NOTE_CACHE_TTL_SECS", 300);
+        let request_timeout_secs = errors.parsed("REQUEST_TIMEOUT_SECS", 15);
+        let slow_query_ms = errors.parsed("SLOW_QUERY_MS", 100);
+        let max_body_bytes = errors.parsed("MAX_BODY_BYTES", 256 * 1024);
+        let max_import_bytes = errors.parsed("MAX_IMPORT_BYTES", 2 * 1024 * 1024);
+        let max_batch = errors.parsed("MAX_BATCH", 100);
+        let poems_dir = errors.parsed("POEMS_DIR", PathBuf::from("./poems"));
+        let poem_cache_ttl_secs = errors.parsed("POEM_CACHE_TTL_SECS", 60);
+        let templates_dir = errors.parsed("TEMPLATES_DIR", PathBuf::from("./templates"));
+        let static_dir = errors.parsed("STATIC_DIR", PathBuf::from("./static"));
+        let cors_allowed_origins: Option<Vec<HeaderValue>> =
+            env::var_os("CORS_ALLOWED_ORIGINS").map(|_| errors.list("CORS_ALLOWED_ORIGINS"));
+        let cors_allow_credentials = errors.parsed("CORS_ALLOW_CREDENTIALS", false);
+        let random_seed = is_set("RANDOM_SEED").then(|| errors.parsed("RANDOM_SEED", 0));
+        // Browsers reject credentials for a wildcard origin, and tower-http
+        // panics on that combination, so refuse it up front
+        let any_origin = match &cors_allowed_origins {
+            None => true,
+            Some(origins) => origins.iter().any(|o| o == "*"),
+        };
+        if cors_allow_credentials && any_origin {
+            errors.invalid.push(
+                "CORS_ALLOW_CREDENTIALS needs an explicit CORS_ALLOWED_ORIGINS list".to_string(),
+            );
+        }
+
+        if !errors.missing.is_empty() || !errors.invalid.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Config {
+            database_url,
+            database_replica_url,
+            db_max_connections,
+            connect_retries,
+            db_acquire_timeout: Duration::from_secs(db_acquire_timeout_secs),
+            db_idle_timeout: Duration::from_secs(db_idle_timeout_secs),
+            db_max_lifetime: Duration::from_secs(db_max_lifetime_secs),
+            redis_url,
+            bind_addr,
+            jwt_secret,
+            redis_max_connections,
+            rate_limit_per_minute,
+            trust_proxy,
+            shutdown_timeout: Duration::from_secs(shutdown_timeout_secs),
+            note_cache_ttl: Duration::from_secs(note_cache_ttl_secs),
+            cors_allowed_origins,
+            cors_allow_credentials,
+            max_body_bytes,
+            max_import_bytes,
+            max_batch,
+            request_timeout: Duration::from_secs(request_timeout_secs),
+            slow_query_threshold: Duration::from_millis(slow_query_ms),
+            poems_dir,
+            poem_cache_ttl: Duration::from_secs(poem_cache_ttl_secs),
+            templates_dir,
+            static_dir,
+            random_seed,
+        })
+    }
+}
+
+impl ConfigError {
+    fn required(&mut self, name: &'static str) -> String {
+        match env::var(name) {
+            Ok(value) if !value.is_empty() => value,
+            _ => {
+                self.missing.push(name);
+                String::new()
+            }
+        }
+    }
+
+    // A required URL, also reported when its scheme is not one we can
+    // connect with, e.g. a Postgres URL left over in DATABASE_URL
+    fn url(&mut self, name: &'static str, schemes: &[&str]) -> String {
+        let value = self.required(name);
+        if !value.is_empty() && !has_scheme(&value, schemes) {
+            self.invalid.push(format!(
+                "{} must start with {}",
+                name,
+                schemes
+                    .iter()
+                    .map(|scheme| format!("{}://", scheme))
+                    .collect::<Vec<_>>()
+                    .join(" or ")
+            ));
+        }
+        value
+    }
+
+    fn parsed<T: FromStr>(&mut self, name: &'static str, default: T) -> T {
+        match env::var(name) {
+            Ok(value) => value.parse().unwrap_or_else(|_| {
+                self.invalid
+                    .push(format!("{} has an invalid value: {:?}", name, value));
+                default
+            }),
+            Err(_) => default,
+        }
+    }
+
+    // Comma separated values, blanks are skipped
+    fn list<T: FromStr>(&mut self, name: &'static str) -> Vec<T> {
+        let Ok(value) = env::var(name) else {
+            return Vec::new();
+        };
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .filter_map(|item| match item.parse() {
+                Ok(parsed) => Some(parsed),
+                Err(_) => {
+                    self.invalid
+                        .push(format!("{} has an invalid entry: {:?}", name, item));
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+// For optional settings, where `NAME=` in a .env file means unset
+fn is_set(name: &str) -> bool {
+    env::var_os(name).is_some_and(|value| !value.is_empty())
+}
+
+fn has_scheme(url: &str, schemes: &[&str]) -> bool {
+    url.split_once("://")
+        .is_some_and(|(scheme, _)| schemes.contains(&scheme))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_list_every_problem_at_once() {
+        let errors = ConfigError {
+            missing: vec!["DATABASE_URL", "REDIS_URL"],
+            invalid: vec!["PORT has an invalid value: \"http\"".to_string()],
+        };
+        assert_eq!(
+            errors.to_string(),
+            "missing required environment variables: DATABASE_URL, REDIS_URL; \
+             PORT has an invalid value: \"http\""
+        );
+    }
+
+    // The variable is only used by this test
+    #[test]
+    fn blank_optional_settings_count_as_unset() {
+        let name = "CONFIG_TEST_OPTIONAL_SETTING";
+        assert!(!is_set(name));
+        env::set_var(name, "");
+        assert!(!is_set(name));
+        env::set_var(name, "mysql://replica/notes");
+        assert!(is_set(name));
+        env::remove_var(name);
+    }
+
+    #[test]
+    fn urls_need_a_known_scheme() {
+        assert!(has_scheme("mysql://user@localhost/notes", &["mysql"]));
+        assert!(has_scheme("rediss://cache:6380/", &["redis", "rediss"]));
+        assert!(!has_scheme("postgres://localhost/notes", &["mysql"]));
+        assert!(!has_scheme("localhost:6379", &["redis"]));
+    }
+}
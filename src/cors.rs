@@ -0,0 +1,57 @@
+use axum::{
+    extract::Request,
+    http::{header, HeaderName, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::config::Config;
+
+// Browser clients may send their own request id and read the one we answer with
+const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+// Cross-origin policy from CORS_ALLOWED_ORIGINS. Unset means any origin, which
+// is meant for local development; an empty value turns CORS off (None).
+pub fn cors_layer(config: &Config) -> Option<CorsLayer> {
+    let origins = match &config.cors_allowed_origins {
+        None => {
+            tracing::warn!("⚠️ CORS_ALLOWED_ORIGINS is not set, allowing any origin");
+            AllowOrigin::any()
+        }
+        Some(origins) if origins.is_empty() => return None,
+        Some(origins) if origins.iter().any(|o| o == "*") => AllowOrigin::any(),
+        Some(origins) => AllowOrigin::list(origins.clone()),
+    };
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE, X_REQUEST_ID])
+            // Browsers hide response headers that are not listed here
+            .expose_headers([X_REQUEST_ID])
+            .allow_credentials(config.cors_allow_credentials),
+    )
+}
+
+// CorsLayer answers preflight requests with an empty 200, report them as
+// 204 No Content instead. Goes outside the CorsLayer.
+pub async fn preflight_no_content(req: Request, next: Next) -> Response {
+    let is_preflight = req.method() == Method::OPTIONS
+        && req
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+    let mut response = next.run(req).await;
+    if is_preflight && response.status() == StatusCode::OK {
+        *response.status_mut() = StatusCode::NO_CONTENT;
+    }
+    response
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use axum::Router;
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi, ToSchema,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::{
+    handlers::handler,
+    models::{note::NoteModelResponse, schema},
+};
+
+// OpenAPI description of the notes API, served as JSON and through Swagger UI
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        handler::note_list_handler,
+        handler::search_notes_handler,
+        handler::create_note_handler,
+        handler::get_note_handler,
+        handler::edit_note_handler,
+        handler::delete_note_handler,
+    ),
+    components(schemas(
+        NoteModelResponse,
+        schema::CreateNoteSchema,
+        schema::UpdateNoteSchema,
+        NoteEnvelope,
+        NoteListResponse,
+        ErrorResponse,
+        ValidationErrorResponse,
+    )),
+    modifiers(&BearerAuth),
+    tags((name = "notes", description = "Note management"))
+)]
+pub struct ApiDoc;
+
+// `Authorization: Bearer <token>` as issued by POST /api/auth/login
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer_auth",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+        }
+    }
+}
+
+// The handlers build these bodies with `json!`, the types below only
+// describe their shape for the spec.
+
+// `{ "status": "success", "data": { "note": ... } }`
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct NoteEnvelope {
+    #[schema(example = "success")]
+    status: String,
+    data: NoteData,
+}
+
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct NoteData {
+    note: NoteModelResponse,
+}
+
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct NoteListResponse {
+    data: Vec<NoteModelResponse>,
+    page: i64,
+    limit: i64,
+    // Only with `count=true`
+    total: Option<i64>,
+}
+
+// Body of every AppError response
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct ErrorResponse {
+    error: String,
+}
+
+// 422 body, each invalid field maps to its messages
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct ValidationErrorResponse {
+    #[schema(example = "Validation failed")]
+    error: String,
+    fields: HashMap<String, Vec<String>>,
+}
+
+// Swagger UI at /swagger-ui, backed by the spec at /api-docs/openapi.json
+pub fn docs_router() -> Router {
+    SwaggerUi::new("/swagger-ui")
+        .url("/api-docs/openapi.json", ApiDoc::openapi())
+        .into()
+}
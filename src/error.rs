@@ -0,0 +1,188 @@
+use axum::{
+    http::{header::RETRY_AFTER, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use thiserror::Error;
+
+// Application wide error type. Handlers return `Result<T, AppError>` so the
+// `?` operator can be used and every failure ends up in the same JSON shape.
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+    #[error("Redis unavailable: {0}")]
+    RedisPool(#[from] deadpool_redis::PoolError),
+    #[error("Error while accessing file: {0}")]
+    FileAccess(#[from] tokio::io::Error),
+    // A file that was read but isn't text, with its path
+    #[error("File {path} is not valid UTF-8: {source}")]
+    Encoding {
+        path: String,
+        source: std::string::FromUtf8Error,
+    },
+    #[error("Error in YAML file: {0}")]
+    YamlParse(#[from] serde_yaml::Error),
+    #[error("Template error: {0}")]
+    Template(#[from] handlebars::RenderError),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Token error: {0}")]
+    Token(#[from] jsonwebtoken::errors::Error),
+    #[error("Password hashing error: {0}")]
+    PasswordHash(#[from] argon2::password_hash::Error),
+    #[error("Background task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    // axum adds the `Allow` header to these responses itself
+    #[error("Method not allowed, the Allow header lists the accepted methods")]
+    MethodNotAllowed,
+    // Carries the number of seconds until the client may retry
+    #[error("Too many requests, retry in {0} seconds")]
+    RateLimited(u64),
+    // One item of a bulk request failed, reported with the item's position
+    #[error("Item {index}: {source}")]
+    BulkItem { index: usize, source: Box<AppError> },
+    // Carries the configured limit in bytes
+    #[error("Request body is larger than the {0} byte limit")]
+    PayloadTooLarge(usize),
+    // A batch over MAX_BATCH, with its size and the limit
+    #[error("Batch of {count} notes is over the limit of {limit}")]
+    BatchTooLarge { count: usize, limit: usize },
+    // Carries the configured limit in seconds
+    #[error("Request timed out after {0} seconds")]
+    Timeout(u64),
+    // Field level failures from `validator`, listed in the response body
+    #[error("Validation failed")]
+    Validation(#[from] validator::ValidationErrors),
+}
+
+impl AppError {
+    // Tag an error with the position of the bulk item that caused it
+    pub fn at_index(self, index: usize) -> Self {
+        AppError::BulkItem {
+            index,
+            source: Box::new(self),
+        }
+    }
+
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BulkItem { source, .. } => source.status_code(),
+            AppError::Database(_)
+            | AppError::Redis(_)
+            | AppError::FileAccess(_)
+            | AppError::Encoding { .. }
+            | AppError::YamlParse(_)
+            | AppError::Template(_)
+            | AppError::Json(_)
+            | AppError::Csv(_)
+            | AppError::Token(_)
+            | AppError::PasswordHash(_)
+            | AppError::Task(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::RedisPool(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            AppError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::BatchTooLarge { .. } | AppError::Validation(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        let message = self.to_string();
+        // Logged inside the request span, so the entry carries the request id
+        tracing::error!(status = status.as_u16(), error = %message, "request failed");
+
+        let mut body = json!({ "error": message });
+        let cause = match &self {
+            AppError::BulkItem { index, source } => {
+                body["index"] = json!(index);
+                source.as_ref()
+            }
+            other => other,
+        };
+        if let AppError::Validation(errors) = cause {
+            body["fields"] = field_messages(errors);
+        }
+        let mut response = (status, Json(body)).into_response();
+        if let AppError::RateLimited(retry_after) = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, retry_after.into());
+        }
+        response
+    }
+}
+
+// `{ "title": ["must not be empty"] }`, falling back to the error code when
+// a rule has no message
+fn field_messages(errors: &validator::ValidationErrors) -> serde_json::Value {
+    let fields = errors
+        .field_errors()
+        .into_iter()
+        .map(|(field, errors)| {
+            let messages = errors
+                .iter()
+                .map(|error| match &error.message {
+                    Some(message) => message.to_string(),
+                    None => error.code.to_string(),
+                })
+                .collect::<Vec<_>>();
+            (field.to_string(), json!(messages))
+        })
+        .collect::<serde_json::Map<_, _>>();
+    serde_json::Value::Object(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use validator::Validate;
+
+    use super::*;
+    use crate::models::schema::CreateNoteSchema;
+
+    #[test]
+    fn validation_errors_list_each_failed_field() {
+        let note = CreateNoteSchema {
+            title: " ".to_string(),
+            content: String::new(),
+            is_published: None,
+            tags: Some(vec![String::new()]),
+        };
+        let error = AppError::from(note.validate().unwrap_err());
+        assert_eq!(error.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let fields = field_messages(match &error {
+            AppError::Validation(errors) => errors,
+            _ => unreachable!(),
+        });
+        assert_eq!(fields["title"], json!(["must not be empty"]));
+        assert!(fields["tags"][0].is_string(), "{fields}");
+        assert!(fields.get("content").is_none(), "{fields}");
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequest, FromRequestParts, Request},
+    http::{request::Parts, HeaderMap},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+use crate::{auth::Claims, error::AppError, AppState};
+
+// The logged in user, from the Claims require_auth validated. Take it as a
+// handler argument in place of `Extension<Claims>`:
+//
+//     async fn handler(user: AuthUser) -> String {
+//         user.user_id
+//     }
+//
+// On a route without require_auth there are no claims, and the request is
+// answered with 401 rather than the 500 a missing Extension would give.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user_id: String,
+    // Admins see and change every user's notes
+    pub is_admin: bool,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let claims = parts
+            .extensions
+            .get::<Claims>()
+            .ok_or_else(|| AppError::Unauthorized("Missing bearer token".to_string()))?;
+        Ok(AuthUser {
+            user_id: claims.sub.clone(),
+            is_admin: claims.admin,
+        })
+    }
+}
+
+// The address of the client, for rate limiting and logs. By default that's
+// the peer of the connection, from the ConnectInfo main serves the app with.
+//
+// Behind a load balancer the peer is always the proxy, so with TRUST_PROXY
+// set the address is the last entry of X-Forwarded-For (the one the proxy
+// appended) or else X-Real-IP. Only set it when every request goes through
+// such a proxy: anyone reaching the server directly can put any address in
+// these headers and, for one, get a fresh rate limit with every request.
+//
+// None when there is no address at all, e.g. when tests call the router
+// without connect info.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        Ok(ClientIp(client_ip(
+            &parts.headers,
+            peer,
+            state.config.trust_proxy,
+        )))
+    }
+}
+
+fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>, trust_proxy: bool) -> Option<IpAddr> {
+    if !trust_proxy {
+        return peer;
+    }
+    let forwarded_for = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .next_back()
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse().ok());
+    let real_ip = || {
+        headers
+            .get("x-real-ip")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|ip| ip.trim().parse().ok())
+    };
+    forwarded_for.or_else(real_ip).or(peer)
+}
+
+// `Json<T>` that also runs `validator::Validate` before the handler sees
+// the body. A body that isn't valid JSON for `T` is rejected exactly like
+// `Json<T>` would, a body that fails validation gets the usual 422
+// `AppError::Validation` with the messages of each failing field.
+//
+// Derive `Validate` on the request struct and mark the fields:
+//
+//     #[derive(Deserialize, Validate)]
+//     pub struct CreateThing {
+//         #[validate(length(min = 1, message = "must not be empty"))]
+//         pub name: String,
+//     }
+//
+// then take `ValidatedJson(body): ValidatedJson<CreateThing>` in place of
+// `Json(body): Json<CreateThing>`.
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        value
+            .validate()
+            .map_err(|err| AppError::from(err).into_response())?;
+        Ok(ValidatedJson(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header::CONTENT_TYPE, StatusCode},
+        routing::{get, post},
+        Extension, Router,
+    };
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[derive(Deserialize, Validate)]
+    struct Named {
+        #[validate(length(min = 1, message = "must not be empty"))]
+        name: String,
+    }
+
+    async fn send(body: &'static str) -> (StatusCode, String) {
+        let app = Router::new().route(
+            "/",
+            post(|ValidatedJson(body): ValidatedJson<Named>| async move { body.name }),
+        );
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn valid_bodies_reach_the_handler() {
+        assert_eq!(
+            send(r#"{"name":"ok"}"#).await,
+            (StatusCode::OK, "ok".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_fields_are_a_422_with_field_messages() {
+        let (status, body) = send(r#"{"name":""}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["fields"]["name"][0], "must not be empty");
+    }
+
+    #[tokio::test]
+    async fn malformed_json_is_rejected_like_json() {
+        let (status, _) = send("{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn client_ip_only_trusts_forwarding_headers_behind_a_proxy() {
+        let peer = Some(IpAddr::from([10, 0, 0, 1]));
+        let mut headers = HeaderMap::new();
+        headers.append("x-forwarded-for", "1.1.1.1, 203.0.113.7".parse().unwrap());
+        headers.insert("x-real-ip", "198.51.100.2".parse().unwrap());
+
+        assert_eq!(client_ip(&headers, peer, false), peer);
+        assert_eq!(
+            client_ip(&headers, peer, true),
+            Some(IpAddr::from([203, 0, 113, 7]))
+        );
+
+        headers.remove("x-forwarded-for");
+        assert_eq!(
+            client_ip(&headers, peer, true),
+            Some(IpAddr::from([198, 51, 100, 2]))
+        );
+
+        headers.insert("x-real-ip", "not an ip".parse().unwrap());
+        assert_eq!(client_ip(&headers, peer, true), peer);
+    }
+
+    #[tokio::test]
+    async fn auth_user_comes_from_the_claims() {
+        let app = Router::new().route("/", get(|user: AuthUser| async move { user.user_id }));
+        let request = || Request::builder().uri("/").body(Body::empty()).unwrap();
+
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let claims = Claims {
+            sub: "alice".to_string(),
+            exp: usize::MAX,
+            admin: false,
+        };
+        let response = app
+            .layer(Extension(claims))
+            .oneshot(request())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"alice");
+    }
+}
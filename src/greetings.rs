@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use axum::{extract::State, routing::get, Json, Router};
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::AppError, AppState};
+
+// Redis list of the latest greetings as JSON, newest first, shared by every
+// server instance.
+const GREETINGS_KEY: &str = "greetings";
+
+// Older greetings are trimmed off the list
+const GREETINGS_KEPT: isize = 100;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Greeting {
+    salutation: String,
+    name: String,
+    at: DateTime<Utc>,
+}
+
+pub fn greetings(app_state: Arc<AppState>) -> Router {
+    // GET /greet/history, the last GREETINGS_KEPT greetings, newest first
+    async fn greeting_history(
+        State(state): State<Arc<AppState>>,
+    ) -> Result<Json<Vec<Greeting>>, AppError> {
+        let mut conn = state.redis_conn().await?;
+        let stored: Vec<String> = conn.lrange(GREETINGS_KEY, 0, -1).await?;
+        Ok(Json(parse_greetings(stored)))
+    }
+
+    // Static segments win over `:name`, so this path can't greet "history"
+    Router::new()
+        .route("/greet/history", get(greeting_history))
+        .with_state(app_state)
+}
+
+// Adds the greeting to the history in the background. The greeting is sent
+// either way, a Redis outage only costs the history entry.
+pub fn record_greeting(state: &Arc<AppState>, salutation: &str, name: &str) {
+    let state = state.clone();
+    let greeting = Greeting {
+        salutation: salutation.to_string(),
+        name: name.to_string(),
+        at: Utc::now(),
+    };
+    tokio::spawn(async move {
+        if let Err(err) = push_greeting(&state, &greeting).await {
+            tracing::warn!(error = %err, "greeting not recorded");
+        }
+    });
+}
+
+async fn push_greeting(state: &AppState, greeting: &Greeting) -> Result<(), AppError> {
+    let payload = serde_json::to_string(greeting)?;
+    let mut conn = state.redis_conn().await?;
+    // Push and trim in one MULTI/EXEC so the list never outgrows the cap
+    let _: () = redis::pipe()
+        .atomic()
+        .lpush(GREETINGS_KEY, payload)
+        .ignore()
+        .ltrim(GREETINGS_KEY, 0, GREETINGS_KEPT - 1)
+        .ignore()
+        .query_async(&mut conn)
+        .await?;
+    Ok(())
+}
+
+// Anything else in the list, say written by hand, is skipped
+fn parse_greetings(stored: Vec<String>) -> Vec<Greeting> {
+    stored
+        .iter()
+        .filter_map(|entry| serde_json::from_str(entry).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreadable_entries_are_skipped() {
+        let stored = vec![
+            r#"{"salutation":"Hi","name":"Ann","at":"2026-10-14T12:00:00Z"}"#.to_string(),
+            "Hello, World!".to_string(),
+        ];
+        assert_eq!(
+            parse_greetings(stored),
+            [Greeting {
+                salutation: "Hi".to_string(),
+                name: "Ann".to_string(),
+                at: "2026-10-14T12:00:00Z".parse().unwrap(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+
+use crate::{error::AppError, extractors::AuthUser, models::schema::ExportOptions, AppState};
+
+// Notes read from MySQL per query. Only one page is held in memory at a
+// time, however many notes the caller has.
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+const CSV_HEADER: [&str; 4] = ["id", "title", "content", "created_at"];
+
+// One exported note, the same fields in both formats
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct ExportRecord {
+    id: String,
+    title: String,
+    content: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    fn parse(format: Option<&str>) -> Result<Self, AppError> {
+        match format.map(str::trim) {
+            None | Some("") => Ok(ExportFormat::Csv),
+            Some(format) if format.eq_ignore_ascii_case("csv") => Ok(ExportFormat::Csv),
+            Some(format) if format.eq_ignore_ascii_case("json") => Ok(ExportFormat::Ndjson),
+            Some(other) => Err(AppError::BadRequest(format!(
+                "Unknown export format: {}, expected csv or json",
+                other
+            ))),
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    fn content_disposition(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "attachment; filename=\"notes.csv\"",
+            ExportFormat::Ndjson => "attachment; filename=\"notes.ndjson\"",
+        }
+    }
+
+    // What goes out before the first note, the header row for CSV
+    fn preamble(self) -> Result<Vec<u8>, AppError> {
+        match self {
+            ExportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                writer.write_record(CSV_HEADER)?;
+                Ok(writer
+                    .into_inner()
+                    .map_err(|err| csv::Error::from(err.into_error()))?)
+            }
+            ExportFormat::Ndjson => Ok(Vec::new()),
+        }
+    }
+
+    fn encode(self, notes: &[ExportRecord]) -> Result<Vec<u8>, AppError> {
+        match self {
+            ExportFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(Vec::new());
+                for note in notes {
+                    writer.serialize(note)?;
+                }
+                Ok(writer
+                    .into_inner()
+                    .map_err(|err| csv::Error::from(err.into_error()))?)
+            }
+            ExportFormat::Ndjson => {
+                let mut lines = Vec::new();
+                for note in notes {
+                    serde_json::to_writer(&mut lines, note)?;
+                    lines.push(b'\n');
+                }
+                Ok(lines)
+            }
+        }
+    }
+}
+
+// GET /api/notes/export, the caller's notes (soft-deleted ones left out)
+// as a file download. The body is streamed one page at a time, ordered by
+// id so each page carries on where the previous one stopped. An error
+// after the first bytes went out can only cut the download short, it is
+// logged and the connection closed.
+pub async fn export_notes_handler(
+    opts: Option<Query<ExportOptions>>,
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Response, AppError> {
+    let Query(opts) = opts.unwrap_or_default();
+    let format = ExportFormat::parse(opts.format.as_deref())?;
+    let preamble = format.preamble()?;
+
+    // None once the last page has been read
+    let pages = stream::try_unfold(Some(String::new()), move |after| {
+        let (data, user_id) = (data.clone(), user.user_id.clone());
+        async move {
+            let Some(after) = after else {
+                return Ok(None);
+            };
+            let notes = sqlx::query_as::<_, ExportRecord>(
+                r#"SELECT id, title, content, created_at FROM notes
+                WHERE user_id = ? AND deleted_at IS NULL AND id > ? ORDER BY id LIMIT ?"#,
+            )
+            .bind(&user_id)
+            .bind(&after)
+            .bind(EXPORT_PAGE_SIZE)
+            .fetch_all(&data.db)
+            .await?;
+            let next = match notes.last() {
+                Some(last) if notes.len() as i64 == EXPORT_PAGE_SIZE => Some(last.id.clone()),
+                _ => None,
+            };
+            Ok::<_, AppError>(Some((format.encode(&notes)?, next)))
+        }
+    });
+    let body = stream::once(async move { Ok(preamble) })
+        .chain(pages)
+        .inspect_err(|err| tracing::error!(error = %err, "note export failed"));
+
+    Ok((
+        [
+            (CONTENT_TYPE, format.content_type()),
+            (CONTENT_DISPOSITION, format.content_disposition()),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(title: &str, content: &str) -> ExportRecord {
+        ExportRecord {
+            id: "6a1e4bb4-5d3c-4c36-9a55-0c6f4b4fd1a2".to_string(),
+            title: title.to_string(),
+            content: content.to_string(),
+            created_at: "2026-10-14T12:00:00Z".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn formats_default_to_csv_and_reject_unknown_names() {
+        assert_eq!(ExportFormat::parse(None).unwrap(), ExportFormat::Csv);
+        assert_eq!(
+            ExportFormat::parse(Some("JSON")).unwrap(),
+            ExportFormat::Ndjson
+        );
+        assert!(matches!(
+            ExportFormat::parse(Some("xml")),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn csv_quotes_commas_quotes_and_newlines() {
+        let csv = [
+            ExportFormat::Csv.preamble().unwrap(),
+            ExportFormat::Csv
+                .encode(&[note("a, b", "say \"hi\"\nbye")])
+                .unwrap(),
+        ]
+        .concat();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "id,title,content,created_at\n\
+             6a1e4bb4-5d3c-4c36-9a55-0c6f4b4fd1a2,\"a, b\",\"say \"\"hi\"\"\nbye\",2026-10-14T12:00:00Z\n"
+        );
+    }
+
+    #[test]
+    fn ndjson_has_one_note_per_line() {
+        let lines = ExportFormat::Ndjson
+            .encode(&[note("one", "1"), note("two", "2\n2")])
+            .unwrap();
+        let lines = String::from_utf8(lines).unwrap();
+        let titles = lines
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["title"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, ["one", "two"]);
+        assert!(ExportFormat::Ndjson.preamble().unwrap().is_empty());
+    }
+}
@@ -1,279 +1,1397 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    future::Future,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Arc,
+    time::Instant,
+};
 
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{
+        header::{ETAG, IF_NONE_MATCH, LAST_MODIFIED},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use redis::AsyncCommands;
+use serde::Serialize;
+use sqlx::{MySql, MySqlConnection, MySqlPool, QueryBuilder};
+use validator::Validate;
 
 use crate::{
-    models::model::{NoteModel, NoteModelResponse},
-    models::schema::{CreateNoteSchema, FilterOptions, UpdateNoteSchema},
+    auth,
+    docs::{ErrorResponse, NoteEnvelope, NoteListResponse, ValidationErrorResponse},
+    error::AppError,
+    extractors::{AuthUser, ValidatedJson},
+    handlers::live::{publish_note_event, NoteEvent, NoteEventKind},
+    models::model::UserModel,
+    models::note::{NoteHistoryModel, NoteModel, NoteModelResponse},
+    models::schema::{
+        ClearNotesOptions, CreateNoteSchema, FilterOptions, LoginRequest, RegisterRequest,
+        SearchOptions, TagSchema, UpdateNoteSchema, MAX_TAG_CHARS,
+    },
     AppState,
 };
 
+// GET /livez and /healthcheck/live, answers as long as the process can serve requests. It never
+// looks at MySQL or Redis, so a dependency blip doesn't get the pod killed.
+pub async fn liveness_handler() -> impl IntoResponse {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+// GET /readyz, /healthcheck/ready and /healthcheck, 503 until MySQL and Redis both answer so
+// the orchestrator stops routing traffic here in the meantime
+pub async fn readiness_handler(State(data): State<Arc<AppState>>) -> impl IntoResponse {
+    const MESSAGE: &str = "API Services";
+
+    // Probe every dependency; a failure is reported, never propagated
+    let db = probe(async {
+        sqlx::query("SELECT 1").execute(&data.db).await?;
+        Ok(())
+    })
+    .await;
+    // Only reported with DATABASE_REPLICA_URL, otherwise it is the same pool
+    let db_replica = match data.config.database_replica_url {
+        Some(_) => Some(
+            probe(async {
+                sqlx::query("SELECT 1").execute(&data.db_read).await?;
+                Ok(())
+            })
+            .await,
+        ),
+        None => None,
+    };
+    let redis = probe(async {
+        let mut conn = data.redis_conn().await?;
+        redis::cmd("PING").query_async::<String>(&mut conn).await?;
+        Ok(())
+    })
+    .await;
+
+    let replica_healthy = db_replica.as_ref().is_none_or(DependencyHealth::is_healthy);
+    let (status_code, status) = if db.is_healthy() && replica_healthy && redis.is_healthy() {
+        (StatusCode::OK, "ok")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "degraded")
+    };
+
+    let mut json_response = serde_json::json!({
+        "status": status,
+        "message": MESSAGE,
+        "db": db,
+        "redis": redis,
+    });
+    if let Some(db_replica) = db_replica {
+        json_response["db_replica"] = serde_json::json!(db_replica);
+    }
+
+    (status_code, Json(json_response))
+}
+
+#[derive(Serialize)]
+struct DependencyHealth {
+    status: &'static str,
+    latency_ms: u128,
+}
+
+impl DependencyHealth {
+    fn is_healthy(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+// Time a dependency check and turn its outcome into a status entry
+async fn probe<F>(check: F) -> DependencyHealth
+where
+    F: Future<Output = Result<(), AppError>>,
+{
+    let started = Instant::now();
+    let result = check.await;
+    DependencyHealth {
+        status: if result.is_ok() { "ok" } else { "down" },
+        latency_ms: started.elapsed().as_millis(),
+    }
+}
+
+pub async fn login_handler(
+    State(data): State<Arc<AppState>>,
+    Json(body): Json<LoginRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let user = sqlx::query_as::<_, UserModel>(r#"SELECT * FROM users WHERE email = ?"#)
+        .bind(normalize_email(&body.email))
+        .fetch_optional(&data.db)
+        .await?;
+
+    // Unknown user and wrong password get the same answer so the response
+    // doesn't reveal which emails are registered. An unknown email is still
+    // checked, against a dummy hash, so it takes as long as a wrong password.
+    let invalid_credentials = || AppError::Unauthorized("Invalid email or password".to_string());
+
+    // argon2 is deliberately slow, keep it off the async worker threads
+    let password = body.password;
+    let password_hash = match &user {
+        Some(user) => user.password_hash.clone(),
+        None => auth::DUMMY_PASSWORD_HASH.to_string(),
+    };
+    let verified =
+        tokio::task::spawn_blocking(move || auth::verify_password(&password, &password_hash))
+            .await
+            .unwrap_or(false);
+    let user = match user {
+        Some(user) if verified => user,
+        _ => return Err(invalid_credentials()),
+    };
+
+    let (token, _) = auth::issue_token(&user.id, user.is_admin != 0, &data.config.jwt_secret)?;
+
+    let json_response = serde_json::json!({
+        "token": token,
+        "expires_in": auth::TOKEN_TTL_SECS,
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn register_handler(
+    State(data): State<Arc<AppState>>,
+    ValidatedJson(body): ValidatedJson<RegisterRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let email = normalize_email(&body.email);
+
+    // same reason as in login_handler
+    let password = body.password;
+    let password_hash =
+        tokio::task::spawn_blocking(move || auth::hash_password(&password)).await??;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let query_result =
+        sqlx::query(r#"INSERT INTO users (id, email, password_hash) VALUES (?, ?, ?)"#)
+            .bind(&id)
+            .bind(&email)
+            .bind(&password_hash)
+            .execute(&data.db)
+            .await;
+
+    if let Err(err) = query_result {
+        if err.to_string().contains("Duplicate entry") {
+            return Err(AppError::Conflict("Email already registered".to_string()));
+        }
+
+        return Err(err.into());
+    }
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "user": serde_json::json!({
+                "id": id,
+                "email": email,
+            })
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+// Emails are matched case-insensitively and without surrounding whitespace
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+// Page size used when `limit` is missing or not positive
+const DEFAULT_PAGE_SIZE: i64 = 20;
+// Largest page a client may request
+const MAX_PAGE_SIZE: i64 = 100;
+
+#[utoipa::path(
+    get,
+    path = "/api/notes",
+    tag = "notes",
+    params(FilterOptions),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "One page of the caller's notes", body = NoteListResponse),
+        (status = 400, description = "Unknown sort field or order", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse)
+    )
+)]
 pub async fn note_list_handler(
     opts: Option<Query<FilterOptions>>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    // Param
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    // Param (invalid values fall back to the defaults)
     let Query(opts) = opts.unwrap_or_default();
 
-    let limit = opts.limit.unwrap_or(10);
-    let offset = (opts.page.unwrap_or(1) - 1) * limit;
+    let limit = match opts.limit {
+        Some(limit) if limit > 0 => limit.min(MAX_PAGE_SIZE),
+        _ => DEFAULT_PAGE_SIZE,
+    };
+    let page = opts.page.filter(|&page| page > 0).unwrap_or(1);
+    let offset = (page - 1) * limit;
+    let order_by = sort_clause(opts.sort.as_deref(), opts.order.as_deref())?;
+
+    // Query built at runtime since the filters are optional
+    let mut query = QueryBuilder::<MySql>::new("SELECT * FROM notes");
+    push_note_filters(&mut query, &user, &opts);
+    query
+        .push(" ORDER BY ")
+        .push(&order_by)
+        .push(" LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+    // Like the other read-only handlers this may miss a write from the
+    // last moments when a replica is configured
+    let notes = query
+        .build_query_as::<NoteModel>()
+        .fetch_all(&data.db_read)
+        .await?;
+
+    // Response
+    let note_responses = notes
+        .iter()
+        .map(to_note_response)
+        .collect::<Vec<NoteModelResponse>>();
+
+    let mut json_response = serde_json::json!({
+        "data": note_responses,
+        "page": page,
+        "limit": limit,
+    });
+
+    // only on request, total uses the same filters so it matches what the
+    // pages add up to
+    if opts.count.unwrap_or(false) {
+        let mut count = QueryBuilder::<MySql>::new("SELECT COUNT(*) FROM notes");
+        push_note_filters(&mut count, &user, &opts);
+        let total: i64 = count.build_query_scalar().fetch_one(&data.db_read).await?;
+        json_response["total"] = serde_json::json!(total);
+    }
+
+    Ok(Json(json_response))
+}
+
+// DELETE /api/notes, admin only (require_admin), for resetting test
+// environments. Hard-deletes every note of every user, their tag links go
+// with them through ON DELETE CASCADE.
+pub async fn clear_notes_handler(
+    opts: Option<Query<ClearNotesOptions>>,
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(opts) = opts.unwrap_or_default();
+    if opts.confirm != Some(true) {
+        return Err(AppError::BadRequest(
+            "Deleting every note needs confirm=true".to_string(),
+        ));
+    }
+
+    let deleted = sqlx::query(r#"DELETE FROM notes"#)
+        .execute(&data.db)
+        .await?
+        .rows_affected();
+    tracing::warn!(user_id = %user.user_id, deleted, "cleared all notes");
+    invalidate_all_cached_notes(&data).await;
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": { "deleted": deleted }
+    });
+
+    Ok(Json(json_response))
+}
+
+// Column names can't be bound, so only these fixed clauses reach the SQL.
+// Dates default to newest first and titles to A-Z. The id tie breaker
+// keeps notes with equal values in the same order on every page.
+fn sort_clause(sort: Option<&str>, order: Option<&str>) -> Result<String, AppError> {
+    let (column, default_order) = match sort.map(str::trim) {
+        None | Some("") | Some("created_at") => ("created_at", "DESC"),
+        Some("updated_at") => ("updated_at", "DESC"),
+        Some("title") => ("title", "ASC"),
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Unknown sort field: {}, expected created_at, updated_at or title",
+                other
+            )))
+        }
+    };
+    let direction = match order.map(str::trim) {
+        None | Some("") => default_order,
+        Some(order) if order.eq_ignore_ascii_case("asc") => "ASC",
+        Some(order) if order.eq_ignore_ascii_case("desc") => "DESC",
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Unknown sort order: {}, expected asc or desc",
+                other
+            )))
+        }
+    };
+    Ok(format!("{column} {direction}, id {direction}"))
+}
+
+// WHERE clause shared by the list query and its count. Admins list every
+// user's notes.
+fn push_note_filters(query: &mut QueryBuilder<'_, MySql>, user: &AuthUser, opts: &FilterOptions) {
+    query
+        .push(" WHERE (")
+        .push_bind(user.is_admin)
+        .push(" OR user_id = ")
+        .push_bind(user.user_id.clone())
+        .push(")");
+
+    if !opts.include_deleted.unwrap_or(false) {
+        query.push(" AND deleted_at IS NULL");
+    }
+
+    if let Some(q) = opts.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let pattern = like_pattern(q);
+        query
+            .push(" AND (title LIKE ")
+            .push_bind(pattern.clone())
+            .push(" OR content LIKE ")
+            .push_bind(pattern)
+            .push(")");
+    }
+
+    if let Some(tag) = opts.tag.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        query
+            .push(
+                " AND id IN (SELECT note_tags.note_id FROM note_tags \
+                 JOIN tags ON tags.id = note_tags.tag_id WHERE tags.name = ",
+            )
+            .push_bind(tag.to_string())
+            .push(")");
+    }
+}
+
+// Substring pattern for LIKE. Wildcards in the user's input are escaped so
+// they match literally.
+fn like_pattern(term: &str) -> String {
+    let escaped = term
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+// Largest page of search results, also the default, so a broad term can't
+// scan the whole table
+const SEARCH_LIMIT: i64 = 50;
 
-    // Query with macro
-    let notes = sqlx::query_as!(
-        NoteModel,
-        r#"SELECT * FROM notes ORDER by id LIMIT ? OFFSET ?"#,
-        limit as i32,
-        offset as i32
+#[utoipa::path(
+    get,
+    path = "/api/notes/search",
+    tag = "notes",
+    params(SearchOptions),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "One page of the caller's notes matching `q`", body = NoteListResponse),
+        (status = 400, description = "Missing or blank `q`", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse)
     )
-    .fetch_all(&data.db)
-    .await
-    .map_err(|e| {
-        let error_response = serde_json::json!({
-            "status": "error",
-            "message": format!("Database error: { }", e),
-        });
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-    })?;
+)]
+pub async fn search_notes_handler(
+    opts: Option<Query<SearchOptions>>,
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    // Param
+    let Query(opts) = opts.unwrap_or_default();
+    let q = opts.q.as_deref().map(str::trim).unwrap_or_default();
+    if q.is_empty() {
+        return Err(AppError::BadRequest(
+            "Query parameter 'q' must not be empty".to_string(),
+        ));
+    }
+    let limit = match opts.limit {
+        Some(limit) if limit > 0 => limit.min(SEARCH_LIMIT),
+        _ => SEARCH_LIMIT,
+    };
+    let page = opts.page.filter(|&page| page > 0).unwrap_or(1);
+    let offset = (page - 1) * limit;
+
+    let pattern = like_pattern(q);
+    let notes = sqlx::query_as::<_, NoteModel>(
+        r#"SELECT * FROM notes WHERE user_id = ? AND deleted_at IS NULL AND (title LIKE ? OR content LIKE ?) ORDER by id LIMIT ? OFFSET ?"#,
+    )
+    .bind(&user.user_id)
+    .bind(&pattern)
+    .bind(&pattern)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&data.db_read)
+    .await?;
 
     // Response
     let note_responses = notes
         .iter()
-        .map(|note| to_note_response(&note))
+        .map(to_note_response)
         .collect::<Vec<NoteModelResponse>>();
 
+    // Same envelope as the list
     let json_response = serde_json::json!({
-        "status": "ok",
-        "count": note_responses.len(),
-        "notes": note_responses
+        "data": note_responses,
+        "page": page,
+        "limit": limit,
     });
 
     Ok(Json(json_response))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/notes",
+    tag = "notes",
+    request_body = CreateNoteSchema,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Note created", body = NoteEnvelope),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 409, description = "Title already in use", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ValidationErrorResponse)
+    )
+)]
 pub async fn create_note_handler(
     State(data): State<Arc<AppState>>,
-    Json(body): Json<CreateNoteSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    // Insert
+    user: AuthUser,
+    // validated before any query, so bad input costs no round trip
+    ValidatedJson(body): ValidatedJson<CreateNoteSchema>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    tracing::info!(user_id = %user.user_id, "creating note");
+
+    // Insert the note and its tags together, dropping the transaction on an
+    // early return rolls everything back
+    let mut tx = data.db.begin().await?;
+    let id = insert_note_with_tags(&mut tx, &user.user_id, &body).await?;
+
+    // Read back inside the transaction, the id is generated here rather
+    // than by AUTO_INCREMENT, so it is known without LAST_INSERT_ID()
+    let note = sqlx::query_as::<_, NoteModel>(r#"SELECT * FROM notes WHERE id = ?"#)
+        .bind(&id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let mut note_response = to_note_response(&note);
+    note_response.tags = Some(fetch_note_tags(&mut *tx, &note.id).await?);
+    tx.commit().await?;
+
+    publish_note_event(
+        &data,
+        &NoteEvent {
+            kind: NoteEventKind::Created,
+            note_id: note.id.clone(),
+            user_id: user.user_id.clone(),
+            note: Some(note_response.clone()),
+        },
+    )
+    .await;
+
+    let note_response = serde_json::json!({
+            "status": "success",
+            "data": serde_json::json!({
+                "note": note_response
+        })
+    });
+
+    Ok(Json(note_response))
+}
+
+// Largest batch accepted by bulk_create_notes_handler
+const MAX_BULK_NOTES: usize = 500;
+
+pub async fn bulk_create_notes_handler(
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(body): Json<Vec<CreateNoteSchema>>,
+) -> Result<impl IntoResponse, AppError> {
+    if body.is_empty() || body.len() > MAX_BULK_NOTES {
+        return Err(AppError::BadRequest(format!(
+            "Send between 1 and {} notes, got {}",
+            MAX_BULK_NOTES,
+            body.len()
+        )));
+    }
+    // everything is checked before the transaction starts
+    for (index, note) in body.iter().enumerate() {
+        note.validate()
+            .map_err(|err| AppError::from(err).at_index(index))?;
+    }
+    tracing::info!(user_id = %user.user_id, count = body.len(), "creating notes in bulk");
+
+    // All or nothing: the first failing item drops the transaction, which
+    // rolls back the ones already inserted
+    let mut tx = data.db.begin().await?;
+    let mut ids = Vec::with_capacity(body.len());
+    for (index, note) in body.iter().enumerate() {
+        let id = insert_note_with_tags(&mut tx, &user.user_id, note)
+            .await
+            .map_err(|err| err.at_index(index))?;
+        ids.push(id);
+    }
+    tx.commit().await?;
+
+    for id in &ids {
+        publish_note_event(
+            &data,
+            &NoteEvent {
+                kind: NoteEventKind::Created,
+                note_id: id.clone(),
+                user_id: user.user_id.clone(),
+                note: None,
+            },
+        )
+        .await;
+    }
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "ids": ids
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+// Insert a note owned by `user_id` plus any tags from the body, returning
+// the new id. Runs on the caller's transaction and never commits it.
+pub(crate) async fn insert_note_with_tags(
+    conn: &mut MySqlConnection,
+    user_id: &str,
+    body: &CreateNoteSchema,
+) -> Result<String, AppError> {
     let id = uuid::Uuid::new_v4().to_string();
-    let query_result = sqlx::query(r#"INSERT INTO notes (id, title, content) VALUES (?, ?, ?)"#)
+    sqlx::query(r#"INSERT INTO notes (id, user_id, title, content) VALUES (?, ?, ?, ?)"#)
         .bind(id.clone())
+        .bind(user_id)
         .bind(body.title.to_string())
         .bind(body.content.to_string())
-        .execute(&data.db)
+        .execute(&mut *conn)
         .await
-        .map_err(|err: sqlx::Error| err.to_string());
+        .map_err(note_insert_error)?;
+    record_history(&mut *conn, &id, user_id, HistoryAction::Create, None).await?;
 
-    // Duplicate err check
-    if let Err(err) = query_result {
-        if err.contains("Duplicate entry") {
-            let error_response = serde_json::json!({
-                "status": "error",
-                "message": "Note already exists",
-            });
-            return Err((StatusCode::CONFLICT, Json(error_response)));
+    for tag in body.tags.iter().flatten() {
+        attach_tag(&mut *conn, &id, tag.trim()).await?;
+    }
+
+    Ok(id)
+}
+
+#[derive(Clone, Copy)]
+enum HistoryAction {
+    Create,
+    Edit,
+    Delete,
+    Restore,
+}
+
+impl HistoryAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            HistoryAction::Create => "create",
+            HistoryAction::Edit => "edit",
+            HistoryAction::Delete => "delete",
+            HistoryAction::Restore => "restore",
         }
+    }
+}
 
-        return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"status": "error","message": format!("{:?}", err)})),
-        ));
+// Appends to note_history on the caller's transaction, so the entry is
+// only kept when the change itself commits. `previous` is the note as it
+// was before the change, None for a create.
+async fn record_history(
+    conn: &mut MySqlConnection,
+    note_id: &str,
+    user_id: &str,
+    action: HistoryAction,
+    previous: Option<&NoteModel>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"INSERT INTO note_history (note_id, user_id, action, previous_title, previous_content)
+        VALUES (?, ?, ?, ?, ?)"#,
+    )
+    .bind(note_id)
+    .bind(user_id)
+    .bind(action.as_str())
+    .bind(previous.map(|note| note.title.as_str()))
+    .bind(previous.map(|note| note.content.as_str()))
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// Duplicate err check, a title already in use is a 409
+fn note_insert_error(err: sqlx::Error) -> AppError {
+    if err.to_string().contains("Duplicate entry") {
+        return AppError::Conflict("Note already exists".to_string());
+    }
+    err.into()
+}
+
+// POST /api/notes/batch. Unlike bulk_create_notes_handler the notes go in
+// with a single multi-row INSERT and the created notes come back in full,
+// in the order they were sent. Still all or nothing.
+pub async fn batch_create_notes_handler(
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(body): Json<Vec<CreateNoteSchema>>,
+) -> Result<impl IntoResponse, AppError> {
+    let limit = data.config.max_batch;
+    if body.len() > limit {
+        return Err(AppError::BatchTooLarge {
+            count: body.len(),
+            limit,
+        });
+    }
+    if body.is_empty() {
+        return Err(AppError::BadRequest("Send at least one note".to_string()));
+    }
+    for (index, note) in body.iter().enumerate() {
+        note.validate()
+            .map_err(|err| AppError::from(err).at_index(index))?;
     }
+    tracing::info!(user_id = %user.user_id, count = body.len(), "creating a batch of notes");
 
-    // Get insereted note by ID
-    let note = sqlx::query_as!(NoteModel, r#"SELECT * FROM notes WHERE id = ?"#, id)
-        .fetch_one(&data.db)
+    let ids = body
+        .iter()
+        .map(|_| uuid::Uuid::new_v4().to_string())
+        .collect::<Vec<_>>();
+    let mut tx = data.db.begin().await?;
+    let mut insert = QueryBuilder::<MySql>::new("INSERT INTO notes (id, user_id, title, content) ");
+    insert.push_values(ids.iter().zip(&body), |mut row, (id, note)| {
+        row.push_bind(id.clone())
+            .push_bind(user.user_id.clone())
+            .push_bind(note.title.clone())
+            .push_bind(note.content.clone());
+    });
+    insert
+        .build()
+        .execute(&mut *tx)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", e)})),
-            )
-        })?;
+        .map_err(note_insert_error)?;
+    let mut history =
+        QueryBuilder::<MySql>::new("INSERT INTO note_history (note_id, user_id, action) ");
+    history.push_values(&ids, |mut row, id| {
+        row.push_bind(id.clone())
+            .push_bind(user.user_id.clone())
+            .push_bind(HistoryAction::Create.as_str());
+    });
+    history.build().execute(&mut *tx).await?;
+    for (index, (id, note)) in ids.iter().zip(&body).enumerate() {
+        for tag in note.tags.iter().flatten() {
+            attach_tag(&mut tx, id, tag.trim())
+                .await
+                .map_err(|err| err.at_index(index))?;
+        }
+    }
 
-    let note_response = serde_json::json!({
-            "status": "success",
-            "data": serde_json::json!({
-                "note": to_note_response(&note)
+    let mut select = QueryBuilder::<MySql>::new("SELECT * FROM notes WHERE id IN (");
+    let mut separated = select.separated(", ");
+    for id in &ids {
+        separated.push_bind(id.clone());
+    }
+    select.push(")");
+    let mut notes = select
+        .build_query_as::<NoteModel>()
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|note| (note.id.clone(), note))
+        .collect::<HashMap<_, _>>();
+    let mut note_responses = Vec::with_capacity(ids.len());
+    for (id, note) in ids.iter().zip(&body) {
+        // Inserted by this very transaction, missing would be a server bug
+        let model = notes.remove(id).ok_or(sqlx::Error::RowNotFound)?;
+        let mut note_response = to_note_response(&model);
+        // Notes sent without tags have none, no need to ask
+        note_response.tags = Some(match &note.tags {
+            Some(tags) if !tags.is_empty() => fetch_note_tags(&mut *tx, id).await?,
+            _ => Vec::new(),
+        });
+        note_responses.push(note_response);
+    }
+    tx.commit().await?;
+
+    for note_response in &note_responses {
+        publish_note_event(
+            &data,
+            &NoteEvent {
+                kind: NoteEventKind::Created,
+                note_id: note_response.id.clone(),
+                user_id: user.user_id.clone(),
+                note: Some(note_response.clone()),
+            },
+        )
+        .await;
+    }
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "notes": note_responses
         })
     });
 
-    Ok(Json(note_response))
+    Ok((StatusCode::CREATED, Json(json_response)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/notes/{id}",
+    tag = "notes",
+    params(("id" = uuid::Uuid, Path, description = "Note id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The note with its tags, with ETag and Last-Modified headers", body = NoteEnvelope),
+        (status = 304, description = "The note still matches the ETag sent in If-None-Match"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "No such note", body = ErrorResponse)
+    )
+)]
 pub async fn get_note_handler(
     Path(id): Path<uuid::Uuid>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    // get using query macro
-    let query_result = sqlx::query_as!(
-        NoteModel,
-        r#"SELECT * FROM notes WHERE id = ?"#,
-        id.to_string()
+    user: AuthUser,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    // served from Redis when possible, MySQL stays the source of truth. The
+    // cache is per owner, so admins reading any note go straight to MySQL.
+    if !user.is_admin {
+        if let Some(note) = read_cached_note(&data, &user.user_id, &id).await {
+            return conditional_note_response(&headers, note);
+        }
+    }
+
+    // What a miss reads goes into the cache, so it comes from the primary. A
+    // lagging replica could hand back the note from before an edit and
+    // cache it past that edit's invalidation. Admin reads aren't cached and
+    // can use the replica.
+    let db = match user.is_admin {
+        true => &data.db_read,
+        false => &data.db,
+    };
+
+    // someone else's note is a plain 404, so ids of other users' notes
+    // can't be probed
+    let query_result = sqlx::query_as::<_, NoteModel>(
+        r#"SELECT * FROM notes WHERE id = ? AND (? OR user_id = ?) AND deleted_at IS NULL"#,
     )
-    .fetch_one(&data.db)
+    .bind(id.to_string())
+    .bind(user.is_admin)
+    .bind(&user.user_id)
+    .fetch_one(db)
     .await;
 
     // check & response
-    match query_result {
-        Ok(note) => {
-            let note_response = serde_json::json!({
-                "status": "success",
-                "data": serde_json::json!({
-                    "note": to_note_response(&note)
-                })
-            });
-
-            return Ok(Json(note_response));
-        }
-        Err(sqlx::Error::RowNotFound) => {
-            let error_response = serde_json::json!({
-                "status": "fail",
-                "message": format!("Note with ID: {} not found", id)
-            });
-            return Err((StatusCode::NOT_FOUND, Json(error_response)));
-        }
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", e)})),
-            ));
-        }
+    let note = match query_result {
+        Ok(note) => note,
+        Err(sqlx::Error::RowNotFound) => return Err(note_not_found(&id)),
+        Err(e) => return Err(e.into()),
     };
+
+    let mut note_response = to_note_response(&note);
+    note_response.tags = Some(fetch_note_tags(db, &note.id).await?);
+    if !user.is_admin {
+        write_cached_note(&data, &user.user_id, &id, &note_response).await;
+    }
+
+    conditional_note_response(&headers, note_response)
+}
+
+// 200 with the note, or an empty 304 when If-None-Match already names its
+// ETag. Both carry ETag and Last-Modified so clients can keep revalidating.
+fn conditional_note_response(
+    headers: &HeaderMap,
+    note: NoteModelResponse,
+) -> Result<Response, AppError> {
+    let etag = note_etag(&note)?;
+    let last_modified = note
+        .updated_at
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string();
+    let validators = [
+        (
+            ETAG,
+            HeaderValue::from_str(&etag).expect("hex ETag is a valid header"),
+        ),
+        (
+            LAST_MODIFIED,
+            HeaderValue::from_str(&last_modified).expect("HTTP date is a valid header"),
+        ),
+    ];
+
+    let if_none_match = headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok());
+    if if_none_match.is_some_and(|value| etag_matches(value, &etag)) {
+        return Ok((StatusCode::NOT_MODIFIED, validators).into_response());
+    }
+
+    let body = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "note": note
+        })
+    });
+    Ok((validators, Json(body)).into_response())
+}
+
+// Hash of the note as served, tags and updated_at included, so any change
+// to the response changes the tag. DefaultHasher is only stable within one
+// build; after a deploy clients simply download the note once more.
+fn note_etag(note: &NoteModelResponse) -> Result<String, AppError> {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(note)?.hash(&mut hasher);
+    Ok(format!("\"{:016x}\"", hasher.finish()))
+}
+
+// If-None-Match is `*` or a comma separated list of tags, compared weakly
+// as RFC 9110 asks for GET, so `W/"abc"` matches `"abc"`.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
 }
 
+#[utoipa::path(
+    patch,
+    path = "/api/notes/{id}",
+    tag = "notes",
+    params(("id" = uuid::Uuid, Path, description = "Note id")),
+    request_body = UpdateNoteSchema,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Note updated", body = NoteEnvelope),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "No such note", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ValidationErrorResponse)
+    )
+)]
 pub async fn edit_note_handler(
     Path(id): Path<uuid::Uuid>,
     State(data): State<Arc<AppState>>,
-    Json(body): Json<UpdateNoteSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    // validate note with query macro
-    let query_result = sqlx::query_as!(
-        NoteModel,
-        r#"SELECT * FROM notes WHERE id = ?"#,
-        id.to_string()
+    user: AuthUser,
+    ValidatedJson(body): ValidatedJson<UpdateNoteSchema>,
+) -> Result<impl IntoResponse, AppError> {
+    // An empty PATCH is a no-op: no write, history entry or event, and
+    // updated_at stays, so the note comes back exactly as it was
+    if body.is_empty() {
+        let note = sqlx::query_as::<_, NoteModel>(
+            r#"SELECT * FROM notes WHERE id = ? AND (? OR user_id = ?) AND deleted_at IS NULL"#,
+        )
+        .bind(id.to_string())
+        .bind(user.is_admin)
+        .bind(&user.user_id)
+        .fetch_optional(&data.db)
+        .await?
+        .ok_or_else(|| note_not_found(&id))?;
+        let mut note_response = to_note_response(&note);
+        note_response.tags = Some(fetch_note_tags(&data.db, &note.id).await?);
+        return Ok(Json(serde_json::json!({
+            "status": "success",
+            "data": serde_json::json!({
+                "note": note_response
+            })
+        })));
+    }
+
+    // Tags come with the note as in GET, whichever path answers
+    let mut tx = data.db.begin().await?;
+    let updated_note = update_note(&mut tx, &user, &id, &body).await?;
+    let tags = fetch_note_tags(&mut *tx, &updated_note.id).await?;
+    tx.commit().await?;
+
+    let owner = note_owner(&updated_note, &user);
+    invalidate_cached_note(&data, owner, &id).await;
+
+    let mut note_response = to_note_response(&updated_note);
+    note_response.tags = Some(tags);
+    publish_note_event(
+        &data,
+        &NoteEvent {
+            kind: NoteEventKind::Updated,
+            note_id: updated_note.id.clone(),
+            user_id: owner.to_string(),
+            note: Some(note_response.clone()),
+        },
     )
-    .fetch_one(&data.db)
     .await;
 
-    // fetch the result
-    let note = match query_result {
-        Ok(note) => note,
-        Err(sqlx::Error::RowNotFound) => {
-            let error_response = serde_json::json!({
-                "status": "error",
-                "message": format!("Note with ID: {} not found", id)
-            });
-            return Err((StatusCode::NOT_FOUND, Json(error_response)));
-        }
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "status": "error",
-                    "message": format!("{:?}", e)
-                })),
-            ));
-        }
-    };
+    let note_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "note": note_response
+        })
+    });
 
-    // parse data
-    let is_published = body.is_published.unwrap_or(note.is_published != 0);
-    let i8_is_published = is_published as i8;
+    Ok(Json(note_response))
+}
+
+// Read, merge and write back under a row lock. A concurrent edit of the
+// same note waits on FOR UPDATE until this transaction ends, so it merges
+// into our result instead of writing back the fields we just replaced.
+async fn update_note(
+    conn: &mut MySqlConnection,
+    user: &AuthUser,
+    id: &uuid::Uuid,
+    body: &UpdateNoteSchema,
+) -> Result<NoteModel, AppError> {
+    let note = sqlx::query_as::<_, NoteModel>(
+        r#"SELECT * FROM notes WHERE id = ? AND (? OR user_id = ?) AND deleted_at IS NULL FOR UPDATE"#,
+    )
+    .bind(id.to_string())
+    .bind(user.is_admin)
+    .bind(&user.user_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| note_not_found(id))?;
 
     // Update (if empty, use old value)
-    let update_result =
-        sqlx::query(r#"UPDATE notes SET title = ?, content = ?, is_published = ? WHERE id = ?"#)
-            .bind(body.title.to_owned().unwrap_or_else(|| note.title.clone()))
-            .bind(
-                body.content
-                    .to_owned()
-                    .unwrap_or_else(|| note.content.clone()),
-            )
-            .bind(i8_is_published)
-            .bind(id.to_string())
-            .execute(&data.db)
-            .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "status": "error",
-                        "message": format!("{:?}", e)
-                    })),
-                )
-            })?;
-
-    // if no data affected (or deleted when wanted to update)
-    if update_result.rows_affected() == 0 {
-        let error_response = serde_json::json!({
-            "status": "error",
-            "message": format!("Note with ID: {} not found", id)
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
-    }
+    let is_published = body.is_published.unwrap_or(note.is_published != 0);
+    sqlx::query(
+        r#"UPDATE notes SET title = ?, content = ?, is_published = ?, updated_at = NOW() WHERE id = ?"#,
+    )
+    .bind(body.title.as_deref().unwrap_or(&note.title))
+    .bind(body.content.as_deref().unwrap_or(&note.content))
+    .bind(is_published as i8)
+    .bind(&note.id)
+    .execute(&mut *conn)
+    .await?;
+    record_history(
+        &mut *conn,
+        &note.id,
+        &user.user_id,
+        HistoryAction::Edit,
+        Some(&note),
+    )
+    .await?;
+
+    let updated_note = sqlx::query_as::<_, NoteModel>(r#"SELECT * FROM notes WHERE id = ?"#)
+        .bind(&note.id)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(updated_note)
+}
 
-    // get updated data
-    let updated_note = sqlx::query_as!(
-        NoteModel,
-        r#"SELECT * FROM notes WHERE id = ?"#,
-        id.to_string()
+#[utoipa::path(
+    delete,
+    path = "/api/notes/{id}",
+    tag = "notes",
+    params(("id" = uuid::Uuid, Path, description = "Note id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 204, description = "Note soft-deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "No such note", body = ErrorResponse)
     )
-    .fetch_one(&data.db)
-    .await
-    .map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"status": "error","message": format!("{:?}", e)})),
-        )
-    })?;
+)]
+pub async fn delete_note_handler(
+    Path(id): Path<uuid::Uuid>,
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    // soft delete: the row stays and can be restored. Locked first so the
+    // history entry holds the note exactly as it was deleted.
+    let mut tx = data.db.begin().await?;
+    let note = sqlx::query_as::<_, NoteModel>(
+        r#"SELECT * FROM notes WHERE id = ? AND (? OR user_id = ?) AND deleted_at IS NULL FOR UPDATE"#,
+    )
+    .bind(id.to_string())
+    .bind(user.is_admin)
+    .bind(&user.user_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| note_not_found(&id))?;
+    sqlx::query(r#"UPDATE notes SET deleted_at = NOW() WHERE id = ?"#)
+        .bind(&note.id)
+        .execute(&mut *tx)
+        .await?;
+    record_history(
+        &mut tx,
+        &note.id,
+        &user.user_id,
+        HistoryAction::Delete,
+        Some(&note),
+    )
+    .await?;
+    tx.commit().await?;
+
+    let owner = note_owner(&note, &user);
+    invalidate_cached_note(&data, owner, &id).await;
+    publish_note_event(
+        &data,
+        &NoteEvent {
+            kind: NoteEventKind::Deleted,
+            note_id: id.to_string(),
+            user_id: owner.to_string(),
+            note: None,
+        },
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn restore_note_handler(
+    Path(id): Path<uuid::Uuid>,
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // only notes that were actually deleted can be restored
+    let mut tx = data.db.begin().await?;
+    let deleted = sqlx::query_as::<_, NoteModel>(
+        r#"SELECT * FROM notes WHERE id = ? AND user_id = ? AND deleted_at IS NOT NULL FOR UPDATE"#,
+    )
+    .bind(id.to_string())
+    .bind(&user.user_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Deleted note with ID: {} not found", id)))?;
+    sqlx::query(r#"UPDATE notes SET deleted_at = NULL WHERE id = ?"#)
+        .bind(&deleted.id)
+        .execute(&mut *tx)
+        .await?;
+    record_history(
+        &mut tx,
+        &deleted.id,
+        &user.user_id,
+        HistoryAction::Restore,
+        Some(&deleted),
+    )
+    .await?;
+    let note = sqlx::query_as::<_, NoteModel>(r#"SELECT * FROM notes WHERE id = ?"#)
+        .bind(&deleted.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    invalidate_cached_note(&data, &user.user_id, &id).await;
+
+    let note_response = to_note_response(&note);
+    publish_note_event(
+        &data,
+        &NoteEvent {
+            kind: NoteEventKind::Restored,
+            note_id: note.id.clone(),
+            user_id: user.user_id.clone(),
+            note: Some(note_response.clone()),
+        },
+    )
+    .await;
 
     let note_response = serde_json::json!({
         "status": "success",
         "data": serde_json::json!({
-            "note": to_note_response(&updated_note)
+            "note": note_response
         })
     });
 
     Ok(Json(note_response))
 }
 
-pub async fn delete_note_handler(
+// Appended to the title of a duplicated note
+const COPY_SUFFIX: &str = " (copy)";
+
+// POST /api/notes/:id/duplicate, a new note with the content and tags of
+// one of the caller's notes and COPY_SUFFIX after its title. The copy starts
+// unpublished with a history of its own. Copying the same note twice is a
+// 409 like any other reused title.
+pub async fn duplicate_note_handler(
     Path(id): Path<uuid::Uuid>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    // delete with query macro
-    let query_result = sqlx::query!(r#"DELETE FROM notes WHERE id = ?"#, id.to_string())
-        .execute(&data.db)
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "status": "error",
-                    "message": format!("{:?}", e)
-                })),
-            )
-        })?;
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.db.begin().await?;
+    let source = sqlx::query_as::<_, NoteModel>(
+        r#"SELECT * FROM notes WHERE id = ? AND user_id = ? AND deleted_at IS NULL"#,
+    )
+    .bind(id.to_string())
+    .bind(&user.user_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| note_not_found(&id))?;
+
+    // A title near the limit can't take the suffix, that is a 422
+    let copy = CreateNoteSchema {
+        title: format!("{}{}", source.title, COPY_SUFFIX),
+        content: source.content.clone(),
+        is_published: None,
+        tags: Some(fetch_note_tags(&mut *tx, &source.id).await?),
+    };
+    copy.validate()?;
+    let copy_id = insert_note_with_tags(&mut tx, &user.user_id, &copy).await?;
+
+    let note = sqlx::query_as::<_, NoteModel>(r#"SELECT * FROM notes WHERE id = ?"#)
+        .bind(&copy_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let mut note_response = to_note_response(&note);
+    note_response.tags = Some(fetch_note_tags(&mut *tx, &note.id).await?);
+    tx.commit().await?;
+
+    publish_note_event(
+        &data,
+        &NoteEvent {
+            kind: NoteEventKind::Created,
+            note_id: note.id.clone(),
+            user_id: user.user_id.clone(),
+            note: Some(note_response.clone()),
+        },
+    )
+    .await;
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "note": note_response
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+// GET /api/notes/:id/history, oldest change first. Soft-deleted notes keep
+// their history and can still be asked about.
+pub async fn note_history_handler(
+    Path(id): Path<uuid::Uuid>,
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    sqlx::query(r#"SELECT id FROM notes WHERE id = ? AND user_id = ?"#)
+        .bind(id.to_string())
+        .bind(&user.user_id)
+        .fetch_optional(&data.db)
+        .await?
+        .ok_or_else(|| note_not_found(&id))?;
+
+    let history = sqlx::query_as::<_, NoteHistoryModel>(
+        r#"SELECT id, action, user_id, previous_title, previous_content, created_at
+        FROM note_history WHERE note_id = ? ORDER BY id"#,
+    )
+    .bind(id.to_string())
+    .fetch_all(&data.db)
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "history": history
+        })
+    })))
+}
+
+pub async fn attach_tag_handler(
+    Path(id): Path<uuid::Uuid>,
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(body): Json<TagSchema>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let tag = body.tag.trim();
+    if tag.is_empty() {
+        return Err(AppError::BadRequest("Tag must not be empty".to_string()));
+    }
+    if tag.chars().count() > MAX_TAG_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "Tag must be at most {} characters",
+            MAX_TAG_CHARS
+        )));
+    }
+
+    ensure_note_exists(&data.db, &user.user_id, &id).await?;
+
+    let mut conn = data.db.acquire().await?;
+    attach_tag(&mut conn, &id.to_string(), tag).await?;
+
+    invalidate_cached_note(&data, &user.user_id, &id).await;
+
+    let tags = fetch_note_tags(&data.db, &id.to_string()).await?;
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "tags": tags
+        })
+    })))
+}
+
+pub async fn detach_tag_handler(
+    Path((id, tag)): Path<(uuid::Uuid, String)>,
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_note_exists(&data.db, &user.user_id, &id).await?;
+
+    let query_result = sqlx::query(
+        r#"DELETE note_tags FROM note_tags JOIN tags ON tags.id = note_tags.tag_id
+        WHERE note_tags.note_id = ? AND tags.name = ?"#,
+    )
+    .bind(id.to_string())
+    .bind(&tag)
+    .execute(&data.db)
+    .await?;
 
-    // response
     if query_result.rows_affected() == 0 {
-        let error_response = serde_json::json!({
-            "status": "error",
-            "message": format!("Note with ID: {} not found", id)
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+        return Err(AppError::NotFound(format!(
+            "Tag {} is not attached to note with ID: {}",
+            tag, id
+        )));
     }
 
+    invalidate_cached_note(&data, &user.user_id, &id).await;
+
     Ok(StatusCode::NO_CONTENT)
 }
 
+// Create the tag if needed and link it to the note. Attaching a tag that is
+// already there is a no-op. Unlike INSERT IGNORE, ON DUPLICATE KEY only
+// tolerates the duplicate, any other error (e.g. a name too long for the
+// column) still fails the statement.
+async fn attach_tag(conn: &mut MySqlConnection, note_id: &str, tag: &str) -> Result<(), AppError> {
+    sqlx::query(r#"INSERT INTO tags (name) VALUES (?) ON DUPLICATE KEY UPDATE name = name"#)
+        .bind(tag)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        r#"INSERT INTO note_tags (note_id, tag_id) SELECT ?, id FROM tags WHERE name = ?
+        ON DUPLICATE KEY UPDATE tag_id = tag_id"#,
+    )
+    .bind(note_id)
+    .bind(tag)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// Drops every cached note, used after clear_notes_handler so no cached note
+// outlives its row. SCAN rather than KEYS to keep Redis responsive on a large
+// keyspace.
+async fn invalidate_all_cached_notes(data: &AppState) {
+    let result: Result<(), AppError> = async {
+        let mut conn = data.redis_conn().await?;
+        let keys: Vec<String> = {
+            let mut iter = conn.scan_match::<_, String>("note:*").await?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        for chunk in keys.chunks(500) {
+            let _: () = conn.del(chunk).await?;
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(err) = result {
+        tracing::warn!(error = %err, "note cache invalidation failed");
+    }
+}
+
+// Redis key caching the get_note_handler response for a note. The owner is
+// part of the key so a cached note is only ever served back to them.
+fn note_cache_key(user_id: &str, id: &uuid::Uuid) -> String {
+    format!("note:{}:{}", user_id, id)
+}
+
+// The cache is best effort: Redis failures are logged and the caller falls
+// back to MySQL as if the entry were missing.
+async fn read_cached_note(
+    data: &AppState,
+    user_id: &str,
+    id: &uuid::Uuid,
+) -> Option<NoteModelResponse> {
+    let result: Result<Option<String>, AppError> = async {
+        let mut conn = data.redis_conn().await?;
+        Ok(conn.get(note_cache_key(user_id, id)).await?)
+    }
+    .await;
+
+    match result {
+        Ok(cached) => cached.and_then(|json| serde_json::from_str(&json).ok()),
+        Err(err) => {
+            tracing::warn!(error = %err, "note cache read failed");
+            None
+        }
+    }
+}
+
+async fn write_cached_note(
+    data: &AppState,
+    user_id: &str,
+    id: &uuid::Uuid,
+    note: &NoteModelResponse,
+) {
+    let result: Result<(), AppError> = async {
+        let json = serde_json::to_string(note)?;
+        let mut conn = data.redis_conn().await?;
+        let ttl = data.config.note_cache_ttl.as_secs();
+        Ok(conn.set_ex(note_cache_key(user_id, id), json, ttl).await?)
+    }
+    .await;
+
+    if let Err(err) = result {
+        tracing::warn!(error = %err, "note cache write failed");
+    }
+}
+
+// Called after every mutation of a note or its tags
+async fn invalidate_cached_note(data: &AppState, user_id: &str, id: &uuid::Uuid) {
+    let result: Result<(), AppError> = async {
+        let mut conn = data.redis_conn().await?;
+        Ok(conn.del(note_cache_key(user_id, id)).await?)
+    }
+    .await;
+
+    if let Err(err) = result {
+        tracing::warn!(error = %err, "note cache invalidation failed");
+    }
+}
+
+// 404 unless the note exists, belongs to the user and isn't soft-deleted
+async fn ensure_note_exists(
+    db: &MySqlPool,
+    user_id: &str,
+    id: &uuid::Uuid,
+) -> Result<(), AppError> {
+    sqlx::query(r#"SELECT id FROM notes WHERE id = ? AND user_id = ? AND deleted_at IS NULL"#)
+        .bind(id.to_string())
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?
+        .map(|_| ())
+        .ok_or_else(|| note_not_found(id))
+}
+
+async fn fetch_note_tags<'e>(
+    db: impl sqlx::Executor<'e, Database = MySql>,
+    note_id: &str,
+) -> Result<Vec<String>, AppError> {
+    let tags = sqlx::query_scalar(
+        r#"SELECT tags.name FROM tags JOIN note_tags ON note_tags.tag_id = tags.id
+        WHERE note_tags.note_id = ? ORDER BY tags.name"#,
+    )
+    .bind(note_id)
+    .fetch_all(db)
+    .await?;
+    Ok(tags)
+}
+
+// Shared 404 for every handler addressing a single note
+fn note_not_found(id: &uuid::Uuid) -> AppError {
+    AppError::NotFound(format!("Note with ID: {} not found", id))
+}
+
+// Whose cache entry and live events a change belongs to. That's the caller,
+// unless an admin changed someone else's note.
+fn note_owner<'a>(note: &'a NoteModel, user: &'a AuthUser) -> &'a str {
+    note.user_id.as_deref().unwrap_or(&user.user_id)
+}
+
 // Convert DB Model to Response
 fn to_note_response(note: &NoteModel) -> NoteModelResponse {
     NoteModelResponse {
@@ -281,7 +1399,278 @@ fn to_note_response(note: &NoteModel) -> NoteModelResponse {
         title: note.title.to_owned(),
         content: note.content.to_owned(),
         is_published: note.is_published != 0,
-        created_at: note.created_at.unwrap(),
-        updated_at: note.updated_at.unwrap(),
+        created_at: note.created_at,
+        updated_at: note.updated_at,
+        deleted_at: note.deleted_at,
+        tags: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(user_id: &str, is_admin: bool) -> AuthUser {
+        AuthUser {
+            user_id: user_id.to_string(),
+            is_admin,
+        }
+    }
+
+    #[test]
+    fn if_none_match_accepts_lists_weak_tags_and_wildcards() {
+        let etag = "\"0123456789abcdef\"";
+        assert!(etag_matches(etag, etag));
+        assert!(etag_matches("\"other\", W/\"0123456789abcdef\"", etag));
+        assert!(etag_matches("*", etag));
+        assert!(!etag_matches("\"other\"", etag));
+        assert!(!etag_matches("0123456789abcdef", etag));
+    }
+
+    #[test]
+    fn sorting_defaults_to_newest_first_and_rejects_unknown_input() {
+        assert_eq!(sort_clause(None, None).unwrap(), "created_at DESC, id DESC");
+        assert_eq!(
+            sort_clause(Some("title"), None).unwrap(),
+            "title ASC, id ASC"
+        );
+        assert_eq!(
+            sort_clause(Some("updated_at"), Some("ASC")).unwrap(),
+            "updated_at ASC, id ASC"
+        );
+        for (sort, order) in [
+            (Some("id; DROP TABLE notes"), None),
+            (Some("content"), None),
+            (None, Some("sideways")),
+        ] {
+            assert!(
+                matches!(sort_clause(sort, order), Err(AppError::BadRequest(_))),
+                "{sort:?} {order:?}"
+            );
+        }
+    }
+
+    fn note(updated_at: &str) -> NoteModelResponse {
+        let updated_at = updated_at.parse().unwrap();
+        NoteModelResponse {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: "title".to_string(),
+            content: "content".to_string(),
+            is_published: false,
+            created_at: updated_at,
+            updated_at,
+            deleted_at: None,
+            tags: Some(vec!["work".to_string()]),
+        }
+    }
+
+    fn if_none_match(etag: &HeaderValue) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_NONE_MATCH, etag.clone());
+        headers
+    }
+
+    // The client fetches the note, then revalidates with the ETag it got
+    #[tokio::test]
+    async fn revalidating_with_the_returned_etag_is_a_304() {
+        let mut note = note("2026-10-14T12:00:00Z");
+        let response = conditional_note_response(&HeaderMap::new(), note.clone()).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[LAST_MODIFIED],
+            "Wed, 14 Oct 2026 12:00:00 GMT"
+        );
+        let etag = response.headers()[ETAG].clone();
+
+        let response = conditional_note_response(&if_none_match(&etag), note.clone()).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], etag);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        // An edit bumps updated_at, the old ETag no longer matches
+        note.updated_at = "2026-10-14T12:00:01Z".parse().unwrap();
+        let response = conditional_note_response(&if_none_match(&etag), note).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[ETAG], etag);
+    }
+
+    // Needs a MySQL server in strict mode (the default), which makes the
+    // over-long tag fail after the note row has already been inserted.
+    #[sqlx::test(migrations = "./migrations")]
+    #[ignore = "needs a MySQL server, run with DATABASE_URL set and --ignored"]
+    async fn failed_tag_insert_rolls_back_the_note(pool: MySqlPool) {
+        let user_id = uuid::Uuid::new_v4().to_string();
+        sqlx::query(r#"INSERT INTO users (id, email, password_hash) VALUES (?, ?, '')"#)
+            .bind(&user_id)
+            .bind("tx@example.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let body = CreateNoteSchema {
+            title: "rolled back".to_string(),
+            content: "never stored".to_string(),
+            is_published: None,
+            tags: Some(vec!["fine".to_string(), "x".repeat(65)]),
+        };
+        let mut tx = pool.begin().await.unwrap();
+        let result = insert_note_with_tags(&mut tx, &user_id, &body).await;
+        assert!(result.is_err());
+        drop(tx);
+
+        for table in ["notes", "tags", "note_tags", "note_history"] {
+            let rows: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(rows, 0, "{} kept rows from the failed transaction", table);
+        }
     }
-}
\ No newline at end of file
+
+    // Two writers editing different fields of the same note at the same
+    // time. Without the row lock either one can read the note before the
+    // other commits and write the old value of the other field back.
+    #[sqlx::test(migrations = "./migrations")]
+    #[ignore = "needs a MySQL server, run with DATABASE_URL set and --ignored"]
+    async fn concurrent_edits_do_not_lose_updates(pool: MySqlPool) {
+        let user_id = uuid::Uuid::new_v4().to_string();
+        sqlx::query(r#"INSERT INTO users (id, email, password_hash) VALUES (?, ?, '')"#)
+            .bind(&user_id)
+            .bind("race@example.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let body = CreateNoteSchema {
+            title: "title 0".to_string(),
+            content: "content 0".to_string(),
+            is_published: None,
+            tags: None,
+        };
+        let mut conn = pool.acquire().await.unwrap();
+        let id = insert_note_with_tags(&mut conn, &user_id, &body)
+            .await
+            .unwrap();
+        drop(conn);
+        let id: uuid::Uuid = id.parse().unwrap();
+
+        for round in 1..=20 {
+            let edit = |body: UpdateNoteSchema| {
+                let (pool, user) = (pool.clone(), user(&user_id, false));
+                tokio::spawn(async move {
+                    let mut tx = pool.begin().await.unwrap();
+                    update_note(&mut tx, &user, &id, &body).await.unwrap();
+                    tx.commit().await.unwrap();
+                })
+            };
+            let title = edit(UpdateNoteSchema {
+                title: Some(format!("title {}", round)),
+                content: None,
+                is_published: None,
+            });
+            let content = edit(UpdateNoteSchema {
+                title: None,
+                content: Some(format!("content {}", round)),
+                is_published: None,
+            });
+            title.await.unwrap();
+            content.await.unwrap();
+
+            let note = sqlx::query_as::<_, NoteModel>(r#"SELECT * FROM notes WHERE id = ?"#)
+                .bind(id.to_string())
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(note.title, format!("title {}", round));
+            assert_eq!(note.content, format!("content {}", round));
+        }
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    #[ignore = "needs a MySQL server, run with DATABASE_URL set and --ignored"]
+    async fn edits_are_recorded_with_the_previous_content(pool: MySqlPool) {
+        let user_id = uuid::Uuid::new_v4().to_string();
+        sqlx::query(r#"INSERT INTO users (id, email, password_hash) VALUES (?, ?, '')"#)
+            .bind(&user_id)
+            .bind("history@example.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let body = CreateNoteSchema {
+            title: "first title".to_string(),
+            content: "first content".to_string(),
+            is_published: None,
+            tags: None,
+        };
+        let mut tx = pool.begin().await.unwrap();
+        let id = insert_note_with_tags(&mut tx, &user_id, &body)
+            .await
+            .unwrap();
+        let edit = UpdateNoteSchema {
+            title: Some("second title".to_string()),
+            content: None,
+            is_published: None,
+        };
+        update_note(&mut tx, &user(&user_id, false), &id.parse().unwrap(), &edit)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+
+        let history = sqlx::query_as::<_, NoteHistoryModel>(
+            r#"SELECT id, action, user_id, previous_title, previous_content, created_at
+            FROM note_history WHERE note_id = ? ORDER BY id"#,
+        )
+        .bind(&id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let actions = history
+            .iter()
+            .map(|entry| (entry.action.as_str(), entry.previous_title.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(actions, [("create", None), ("edit", Some("first title"))]);
+        assert!(history.iter().all(|entry| entry.user_id == user_id));
+    }
+
+    // Another user's note is a 404 for everyone but admins
+    #[sqlx::test(migrations = "./migrations")]
+    #[ignore = "needs a MySQL server, run with DATABASE_URL set and --ignored"]
+    async fn only_owners_and_admins_can_edit_a_note(pool: MySqlPool) {
+        let owner_id = uuid::Uuid::new_v4().to_string();
+        sqlx::query(r#"INSERT INTO users (id, email, password_hash) VALUES (?, ?, '')"#)
+            .bind(&owner_id)
+            .bind("owner@example.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let body = CreateNoteSchema {
+            title: "mine".to_string(),
+            content: "private".to_string(),
+            is_published: None,
+            tags: None,
+        };
+        let mut tx = pool.begin().await.unwrap();
+        let id: uuid::Uuid = insert_note_with_tags(&mut tx, &owner_id, &body)
+            .await
+            .unwrap()
+            .parse()
+            .unwrap();
+        let edit = UpdateNoteSchema {
+            title: Some("edited".to_string()),
+            content: None,
+            is_published: None,
+        };
+
+        let stranger = uuid::Uuid::new_v4().to_string();
+        let result = update_note(&mut tx, &user(&stranger, false), &id, &edit).await;
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+
+        let note = update_note(&mut tx, &user(&stranger, true), &id, &edit)
+            .await
+            .unwrap();
+        assert_eq!(note.title, "edited");
+        assert_eq!(note.user_id.as_deref(), Some(owner_id.as_str()));
+    }
+}
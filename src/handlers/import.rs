@@ -0,0 +1,226 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{multipart::MultipartError, Multipart, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::{
+    error::AppError,
+    extractors::AuthUser,
+    handlers::handler::insert_note_with_tags,
+    handlers::live::{publish_note_event, NoteEvent, NoteEventKind},
+    models::schema::CreateNoteSchema,
+    AppState,
+};
+
+// Multipart field holding the CSV file
+const FILE_FIELD: &str = "file";
+
+// The columns read from each row. Others, like the id and created_at of
+// a file from GET /api/notes/export, are ignored.
+#[derive(Debug, Deserialize)]
+struct ImportRow {
+    title: String,
+    content: String,
+}
+
+// A row left out of the import and why, by line number in the file
+#[derive(Debug, Serialize, PartialEq)]
+struct SkippedRow {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u64>,
+    error: String,
+}
+
+// What parse_notes read from a file: the notes to insert with their line
+// numbers, and the rows skipped
+struct ParsedImport {
+    notes: Vec<(u64, CreateNoteSchema)>,
+    skipped: Vec<SkippedRow>,
+}
+
+// POST /api/notes/import, multipart with the CSV in the `file` field and
+// a `title,content` header row. Rows that can't be read, fail validation
+// or reuse a title are skipped and listed in `errors`, the rest is
+// inserted in one transaction. The file may be up to MAX_IMPORT_BYTES, the
+// route raises the body limit to match.
+pub async fn import_notes_handler(
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+    mut multipart: Multipart,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let limit = data.config.max_import_bytes;
+    let mut file = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| multipart_error(err, limit))?
+    {
+        if field.name() == Some(FILE_FIELD) {
+            file = Some(
+                field
+                    .bytes()
+                    .await
+                    .map_err(|err| multipart_error(err, limit))?,
+            );
+            break;
+        }
+    }
+    let file = file.ok_or_else(|| {
+        AppError::BadRequest(format!("Expected a CSV file in the `{}` field", FILE_FIELD))
+    })?;
+
+    let ParsedImport { notes, mut skipped } = parse_notes(&file)?;
+    tracing::info!(user_id = %user.user_id, count = notes.len(), "importing notes");
+
+    let mut tx = data.db.begin().await?;
+    let mut ids = Vec::with_capacity(notes.len());
+    for (line, note) in &notes {
+        // MySQL only rolls back the failed statement on a duplicate key,
+        // so the transaction carries on with the next row
+        match insert_note_with_tags(&mut tx, &user.user_id, note).await {
+            Ok(id) => ids.push(id),
+            Err(AppError::Conflict(message)) => skipped.push(SkippedRow {
+                line: Some(*line),
+                error: message,
+            }),
+            Err(err) => return Err(err),
+        }
+    }
+    tx.commit().await?;
+
+    for id in &ids {
+        publish_note_event(
+            &data,
+            &NoteEvent {
+                kind: NoteEventKind::Created,
+                note_id: id.clone(),
+                user_id: user.user_id.clone(),
+                note: None,
+            },
+        )
+        .await;
+    }
+    skipped.sort_by_key(|row| row.line);
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "imported": ids.len(),
+            "skipped": skipped.len(),
+            "errors": skipped
+        })
+    })))
+}
+
+// A file over the limit is the usual JSON 413, with the import limit
+// rather than MAX_BODY_BYTES. Anything else is a broken upload.
+fn multipart_error(err: MultipartError, limit: usize) -> AppError {
+    match err.status() {
+        StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge(limit),
+        _ => AppError::BadRequest(err.body_text()),
+    }
+}
+
+// Only a file without the needed columns fails as a whole
+fn parse_notes(file: &[u8]) -> Result<ParsedImport, AppError> {
+    let mut reader = csv::Reader::from_reader(file);
+    let headers = reader
+        .headers()
+        .map_err(|err| AppError::BadRequest(format!("Unreadable CSV header: {}", err)))?
+        .clone();
+    for column in ["title", "content"] {
+        if !headers.iter().any(|header| header.trim() == column) {
+            return Err(AppError::BadRequest(format!(
+                "The CSV header has no {} column",
+                column
+            )));
+        }
+    }
+
+    let mut notes = Vec::new();
+    let mut skipped = Vec::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                skipped.push(SkippedRow {
+                    line: err.position().map(|position| position.line()),
+                    error: err.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line());
+        let note = record
+            .deserialize::<ImportRow>(Some(&headers))
+            .map_err(|err| err.to_string())
+            .and_then(|row| {
+                let note = CreateNoteSchema {
+                    title: row.title,
+                    content: row.content,
+                    is_published: None,
+                    tags: None,
+                };
+                note.validate().map_err(|err| err.to_string())?;
+                Ok(note)
+            });
+        match note {
+            Ok(note) => notes.push((line, note)),
+            Err(error) => skipped.push(SkippedRow {
+                line: Some(line),
+                error,
+            }),
+        }
+    }
+    Ok(ParsedImport { notes, skipped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_rows_are_skipped_with_their_line() {
+        let file = "title,content\n\
+                    first,one\n\
+                    too,many,fields\n\
+                    ,no title\n\
+                    \"quoted, title\",\"two\nlines\"\n";
+        let ParsedImport { notes, skipped } = parse_notes(file.as_bytes()).unwrap();
+
+        let notes = notes
+            .iter()
+            .map(|(line, note)| (*line, note.title.as_str(), note.content.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            notes,
+            [(2, "first", "one"), (5, "quoted, title", "two\nlines")]
+        );
+        assert_eq!(
+            skipped.iter().map(|row| row.line).collect::<Vec<_>>(),
+            [Some(3), Some(4)]
+        );
+    }
+
+    #[test]
+    fn exported_files_import_as_they_are() {
+        let file = "id,title,content,created_at\n\
+                    6a1e4bb4-5d3c-4c36-9a55-0c6f4b4fd1a2,title,content,2026-10-14T12:00:00Z\n";
+        let ParsedImport { notes, skipped } = parse_notes(file.as_bytes()).unwrap();
+        assert_eq!(notes.len(), 1);
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn files_without_the_columns_are_rejected() {
+        assert!(matches!(
+            parse_notes(b"name,text\na,b\n"),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+}
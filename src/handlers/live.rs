@@ -0,0 +1,215 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::IntoResponse,
+};
+use futures_util::{SinkExt, StreamExt};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError},
+        watch,
+    },
+    task::JoinHandle,
+};
+
+use crate::{error::AppError, extractors::AuthUser, models::note::NoteModelResponse, AppState};
+
+// Redis pub/sub channel carrying every note change
+pub const NOTES_CHANNEL: &str = "notes:events";
+
+// How often the server pings, a client that misses two pings is dropped
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+// Events a socket may fall behind by. A slower client skips the oldest ones
+// instead of holding memory for them.
+const NOTE_EVENTS_BUFFER: usize = 256;
+
+// Longest wait before the relay subscribes again after losing Redis
+const RELAY_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteEventKind {
+    Created,
+    Updated,
+    Deleted,
+    Restored,
+}
+
+// Published as JSON on NOTES_CHANNEL and forwarded as is to the owner's sockets
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoteEvent {
+    pub kind: NoteEventKind,
+    pub note_id: String,
+    pub user_id: String,
+    // The note after the change, missing for deletes and bulk inserts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<NoteModelResponse>,
+}
+
+// Best effort like the note cache: the change is already committed, so a
+// Redis failure only loses the live update and is logged.
+pub async fn publish_note_event(data: &AppState, event: &NoteEvent) {
+    let result: Result<(), AppError> = async {
+        let payload = serde_json::to_string(event)?;
+        let mut conn = data.redis_conn().await?;
+        Ok(conn.publish(NOTES_CHANNEL, payload).await?)
+    }
+    .await;
+
+    if let Err(err) = result {
+        tracing::warn!(error = %err, "publishing note event failed");
+    }
+}
+
+// The in-process side of NOTES_CHANNEL, stored in AppState. The relay is
+// its only sender; every live socket holds a receiver.
+pub fn note_events_channel() -> broadcast::Sender<String> {
+    broadcast::channel(NOTE_EVENTS_BUFFER).0
+}
+
+// One Redis subscription per process, fanned out to the sockets through
+// AppState::note_events. Subscribes again with backoff whenever Redis goes
+// away, and stops once `shutdown` turns true, main waits for it during
+// graceful shutdown.
+pub fn spawn_note_event_relay(
+    data: Arc<AppState>,
+    mut shutdown: watch::Receiver<bool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut delay = Duration::from_secs(1);
+        loop {
+            let started = Instant::now();
+            tokio::select! {
+                // Also done when main dropped the sender
+                _ = shutdown.wait_for(|&stop| stop) => break,
+                result = relay_note_events(&data) => {
+                    if let Err(err) = result {
+                        tracing::warn!(
+                            error = %err,
+                            "note event relay lost Redis, retrying in {:?}",
+                            delay
+                        );
+                    }
+                }
+            }
+            // A subscription that held for a while starts the backoff over
+            if started.elapsed() > RELAY_MAX_BACKOFF {
+                delay = Duration::from_secs(1);
+            }
+            tokio::select! {
+                _ = shutdown.wait_for(|&stop| stop) => break,
+                _ = tokio::time::sleep(delay) => {}
+            }
+            delay = (delay * 2).min(RELAY_MAX_BACKOFF);
+        }
+        tracing::info!("note event relay stopped");
+    })
+}
+
+async fn relay_note_events(data: &AppState) -> Result<(), AppError> {
+    // A subscription holds its connection for good, so it gets its own
+    // rather than one from the pool
+    let client = redis::Client::open(data.config.redis_url.as_str())?;
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(NOTES_CHANNEL).await?;
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        // No receivers just means no socket is open right now
+        let _ = data.note_events.send(message.get_payload()?);
+    }
+    Ok(())
+}
+
+// GET /api/notes/live, streams the caller's note events over a WebSocket
+// exactly as they were published
+pub async fn live_notes_handler(
+    ws: WebSocketUpgrade,
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| async move {
+        forward_note_events(socket, data, user.user_id, |_, payload| payload).await;
+    })
+}
+
+// GET /ws/notes, the same events shaped for UIs as
+// `{ "event": "created", "note_id": "...", "note": { ... } }`
+pub async fn ws_notes_handler(
+    ws: WebSocketUpgrade,
+    State(data): State<Arc<AppState>>,
+    user: AuthUser,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| async move {
+        forward_note_events(socket, data, user.user_id, |event, _| {
+            serde_json::json!({
+                "event": event.kind,
+                "note_id": event.note_id,
+                "note": event.note,
+            })
+            .to_string()
+        })
+        .await;
+    })
+}
+
+// Sends every event of `user_id` through `format` to the socket until the
+// client goes away, answers a close or stops answering pings
+async fn forward_note_events(
+    socket: WebSocket,
+    data: Arc<AppState>,
+    user_id: String,
+    format: impl Fn(NoteEvent, String) -> String,
+) {
+    let mut events = data.note_events.subscribe();
+    let (mut sender, mut receiver) = socket.split();
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    let mut last_pong = Instant::now();
+
+    loop {
+        tokio::select! {
+            received = events.recv() => {
+                let payload = match received {
+                    Ok(payload) => payload,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "live notes socket fell behind, skipping events");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                let Ok(event) = serde_json::from_str::<NoteEvent>(&payload) else {
+                    continue;
+                };
+                if event.user_id != user_id {
+                    continue;
+                }
+                if sender.send(Message::Text(format(event, payload))).await.is_err() {
+                    break;
+                }
+            }
+            incoming = receiver.next() => match incoming {
+                Some(Ok(Message::Pong(_))) => last_pong = Instant::now(),
+                // Client pings are answered by the WebSocket library itself
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = ping.tick() => {
+                if last_pong.elapsed() > PING_INTERVAL * 2 {
+                    break;
+                }
+                if sender.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -1 +1,5 @@
-pub mod handler;
\ No newline at end of file
+pub mod export;
+pub mod handler;
+pub mod import;
+pub mod live;
+pub mod stats;
\ No newline at end of file
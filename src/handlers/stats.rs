@@ -0,0 +1,161 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use axum::{extract::State, Json};
+use chrono::{DateTime, Utc};
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError},
+        watch,
+    },
+    task::JoinHandle,
+};
+
+use crate::{
+    handlers::live::{NoteEvent, NoteEventKind},
+    AppState,
+};
+
+// Note events counted by the stats worker since this process started, for
+// GET /api/notes/stats. Every instance sees every event through Redis, so
+// instances that started together report the same numbers.
+pub struct NoteStats {
+    since: DateTime<Utc>,
+    created: AtomicU64,
+    updated: AtomicU64,
+    deleted: AtomicU64,
+    restored: AtomicU64,
+    // Events the worker fell too far behind to count
+    missed: AtomicU64,
+}
+
+impl NoteStats {
+    pub fn new() -> Self {
+        NoteStats {
+            since: Utc::now(),
+            created: AtomicU64::new(0),
+            updated: AtomicU64::new(0),
+            deleted: AtomicU64::new(0),
+            restored: AtomicU64::new(0),
+            missed: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, kind: &NoteEventKind) {
+        let counter = match kind {
+            NoteEventKind::Created => &self.created,
+            NoteEventKind::Updated => &self.updated,
+            NoteEventKind::Deleted => &self.deleted,
+            NoteEventKind::Restored => &self.restored,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "since": self.since,
+            "created": self.created.load(Ordering::Relaxed),
+            "updated": self.updated.load(Ordering::Relaxed),
+            "deleted": self.deleted.load(Ordering::Relaxed),
+            "restored": self.restored.load(Ordering::Relaxed),
+            "missed": self.missed.load(Ordering::Relaxed),
+        })
+    }
+}
+
+impl Default for NoteStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Counts the events the note event relay receives into AppState::note_stats,
+// off the request path. The relay owns the Redis subscription and
+// reconnects it, this task only reads the in-process channel. It stops once
+// `shutdown` turns true, main waits for it during graceful shutdown.
+pub fn spawn_note_stats_worker(
+    data: Arc<AppState>,
+    shutdown: watch::Receiver<bool>,
+) -> JoinHandle<()> {
+    let events = data.note_events.subscribe();
+    tokio::spawn(async move {
+        count_note_events(events, &data.note_stats, shutdown).await;
+        tracing::info!("note stats worker stopped");
+    })
+}
+
+async fn count_note_events(
+    mut events: broadcast::Receiver<String>,
+    stats: &NoteStats,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            // Also done when main dropped the sender
+            _ = shutdown.wait_for(|&stop| stop) => break,
+            received = events.recv() => match received {
+                Ok(payload) => match serde_json::from_str::<NoteEvent>(&payload) {
+                    Ok(event) => stats.record(&event.kind),
+                    Err(err) => tracing::warn!(error = %err, "skipping malformed note event"),
+                },
+                Err(RecvError::Lagged(missed)) => {
+                    stats.missed.fetch_add(missed, Ordering::Relaxed);
+                }
+                Err(RecvError::Closed) => break,
+            },
+        }
+    }
+}
+
+// GET /api/notes/stats, the counts of every user's note changes. The route
+// is behind require_admin.
+pub async fn note_stats_handler(State(data): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "status": "success",
+        "data": data.note_stats.to_json()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: &str) -> String {
+        format!(r#"{{"kind":"{kind}","note_id":"n","user_id":"u"}}"#)
+    }
+
+    #[tokio::test]
+    async fn every_kind_of_event_is_counted() {
+        let (events, receiver) = broadcast::channel(16);
+        let (_shutdown, shutdown) = watch::channel(false);
+        for kind in ["created", "created", "deleted"] {
+            events.send(event(kind)).unwrap();
+        }
+        events.send("not json".to_string()).unwrap();
+        // The worker drains what is queued, then sees the channel closed
+        drop(events);
+
+        let stats = NoteStats::new();
+        count_note_events(receiver, &stats, shutdown).await;
+        let counts = stats.to_json();
+        assert_eq!(counts["created"], 2);
+        assert_eq!(counts["deleted"], 1);
+        assert_eq!(counts["updated"], 0);
+    }
+
+    #[tokio::test]
+    async fn the_worker_stops_on_shutdown() {
+        let (_events, receiver) = broadcast::channel::<String>(16);
+        let (shutdown, shutdown_receiver) = watch::channel(false);
+        let worker = tokio::spawn(async move {
+            count_note_events(receiver, &NoteStats::new(), shutdown_receiver).await
+        });
+        shutdown.send(true).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(1), worker)
+            .await
+            .expect("worker kept running after shutdown")
+            .unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+pub mod app;
+mod auth;
+mod body_limit;
+pub mod config;
+mod cors;
+mod docs;
+mod error;
+mod extractors;
+mod greetings;
+mod handlers;
+mod lookup;
+mod method_not_allowed;
+mod models;
+mod numbers;
+pub mod poems;
+mod rate_limit;
+mod routes;
+pub mod seed;
+mod static_files;
+pub mod telemetry;
+pub mod templates;
+mod timeout;
+
+use sqlx::mysql::MySqlPool;
+
+use config::Config;
+use error::AppError;
+
+pub use app::build_app;
+pub use handlers::live::{note_events_channel, spawn_note_event_relay};
+pub use handlers::stats::{spawn_note_stats_worker, NoteStats};
+pub use numbers::numbers_channel;
+
+pub struct AppState {
+    pub db: MySqlPool,
+    // Replica for the read-only handlers, which may lag behind db. The same
+    // pool as db when DATABASE_REPLICA_URL is unset.
+    pub db_read: MySqlPool,
+    pub redis: deadpool_redis::Pool,
+    pub poems: poems::PoemCache,
+    pub engine: templates::AppEngine,
+    pub config: Config,
+    // Renders GET /metrics, see telemetry::install_metrics_recorder
+    pub metrics: metrics_exporter_prometheus::PrometheusHandle,
+    // Note events as published on Redis, see handlers::live
+    pub note_events: tokio::sync::broadcast::Sender<String>,
+    // Counted from note_events, see handlers::stats
+    pub note_stats: NoteStats,
+    // The numbers list after each change, see numbers::numbers_channel
+    pub numbers: tokio::sync::watch::Sender<Vec<i32>>,
+}
+
+impl AppState {
+    // Check out a pooled Redis connection. Waits for a free connection when
+    // the pool is exhausted, which gives natural backpressure.
+    async fn redis_conn(&self) -> Result<deadpool_redis::Connection, AppError> {
+        Ok(self.redis.get().await?)
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use redis::AsyncCommands;
+use serde::Serialize;
+
+use crate::{error::AppError, AppState};
+
+// Redis set of the numbers GET /lookup/:number finds, shared by every
+// server instance.
+const LOOKUPS_KEY: &str = "lookups";
+
+#[derive(Serialize)]
+struct LookupResult {
+    number: i32,
+    found: bool,
+}
+
+pub fn lookup(app_state: Arc<AppState>) -> Router {
+    // A lot of types implement IntoResponse, including tuples.
+    // Read more at https://docs.rs/axum/latest/axum/response/trait.IntoResponse.html
+    // Always 200, `found` tells whether the number is registered
+    async fn look_it_up(
+        State(state): State<Arc<AppState>>,
+        Path(number): Path<i32>,
+    ) -> Result<Json<LookupResult>, AppError> {
+        let mut conn = state.redis_conn().await?;
+        let found: bool = conn.sismember(LOOKUPS_KEY, number).await?;
+        Ok(Json(LookupResult { number, found }))
+    }
+
+    // Registers the number, 201 the first time and 200 when it was known
+    async fn register_number(
+        State(state): State<Arc<AppState>>,
+        Path(number): Path<i32>,
+    ) -> Result<impl IntoResponse, AppError> {
+        let mut conn = state.redis_conn().await?;
+        let added: u32 = conn.sadd(LOOKUPS_KEY, number).await?;
+        let status = match added {
+            0 => StatusCode::OK,
+            _ => StatusCode::CREATED,
+        };
+        Ok((
+            status,
+            Json(LookupResult {
+                number,
+                found: true,
+            }),
+        ))
+    }
+
+    // Unregisters the number, 200 whether or not it was registered
+    async fn unregister_number(
+        State(state): State<Arc<AppState>>,
+        Path(number): Path<i32>,
+    ) -> Result<Json<LookupResult>, AppError> {
+        let mut conn = state.redis_conn().await?;
+        let _: u32 = conn.srem(LOOKUPS_KEY, number).await?;
+        Ok(Json(LookupResult {
+            number,
+            found: false,
+        }))
+    }
+
+    Router::new()
+        .route(
+            "/lookup/:number",
+            get(look_it_up)
+                .post(register_number)
+                .delete(unregister_number),
+        )
+        .with_state(app_state)
+}
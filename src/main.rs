@@ -1,278 +1,370 @@
-mod handlers;
-mod models;
-mod routes;
-
-use std::sync::Arc;
-
-use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::{Html, IntoResponse, Response},
-    routing::get,
-    Json, Router,
+use std::{
+    future::{Future, IntoFuture},
+    net::SocketAddr,
+    sync::Arc,
+    time::Duration,
 };
-use rand::Rng;
-use serde::{Deserialize, Serialize};
-use thiserror::Error;
-use tokio::{fs::File, io::AsyncReadExt, sync::RwLock};
 
-use sqlx::mysql::{MySqlPool, MySqlPoolOptions};
+use clap::{Parser, Subcommand};
+use sqlx::{
+    mysql::{MySqlConnectOptions, MySqlPool, MySqlPoolOptions},
+    ConnectOptions,
+};
 use dotenv::dotenv;
 //use std::env;
 
 // use crate::{
 //     handler::{create_note_handler, delete_note_handler, edit_note_handler, get_note_handler, health_check_handler, note_list_handler},
 // }
-use routes::route::create_router;
+use webserver_base::{
+    build_app, config::Config, note_events_channel, numbers_channel, poems, seed,
+    spawn_note_event_relay, spawn_note_stats_worker, telemetry, templates, AppState, NoteStats,
+};
 
-#[derive(Default, Clone)]
-struct AppState2 {
-    numbers: Vec<i32>,
+// Without a command the server starts. The others are for development and
+// admin tasks, they run against the configured database and exit.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-struct AppState {
-    db: MySqlPool,
+#[derive(Subcommand)]
+enum Command {
+    /// Replace every note with a fixed set of sample notes owned by
+    /// dev@example.com (password "password"). Development only.
+    Seed,
 }
 
-// Note that you can use trait objects for shared state, too. This is useful
-// for e.g. mock objects in unit tests. Sample for trait object state:
-// https://github.com/tokio-rs/axum/blob/8854e660e9ab07404e5bb8e30b92311d3848de05/examples/error-handling-and-dependency-injection/src/main.rs#L124
-type AppStateType = Arc<RwLock<AppState2>>;
+// First wait between startup connection attempts, doubled after each one
+const CONNECT_BACKOFF: Duration = Duration::from_secs(1);
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
     dotenv().ok();
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must set");
-    let pool = match MySqlPoolOptions::new()
-        .max_connections(10)
-        .connect(&database_url)
-        .await{
+    telemetry::init_tracing();
+    let metrics_handle = telemetry::install_metrics_recorder();
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::error!("❌ Invalid configuration: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let pool = match connect_db(&config, &config.database_url, "the database").await {
         Ok(pool) => {
-            println!("✅ Connection to the database is successful!");
+            tracing::info!("✅ Connection to the database is successful!");
             pool
         }
         Err(err) => {
-            println!("❌ Failed to connect to the database: {:?}", err);
+            tracing::error!("❌ Failed to connect to the database: {:?}", err);
             std::process::exit(1);
         }
     };
 
-    let pool = Arc::new(AppState{db: pool});
-    let app = Router::new()
-        .route("/", get(hello_world).post(post_hello_world))
-        .route("/healthcheck", get(health_check))
-        .route("/greet/:name", get(greet_path))
-        .route("/greet", get(greet_query).post(greet_body))
-        .route("/lookup/:number", get(look_it_up))
-        .route("/random", get(return_something_random))
-        .merge(numbers_management())
-        .with_state(AppStateType::default())
-        //.with_state(pool)
-        // Let's add additional routes. Note that we can structure complex
-        // routing hierarchies using methods like merge and nest.
-        .merge(pingpong())
-        .nest("/kingkong", kingkong())
-        .merge(poem()
-        .merge(create_router(pool))
-        // .route("/api/notes", post(create_note_handler).get(note_list_handler))
-        // .route(
-        //     "/api/notes/:id",
-        //     get(get_note_handler)
-        //         .patch(edit_note_handler)
-        //         .delete(delete_note_handler),
-        // )
-    );
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
-}
+    // Bring the schema up to date before anything can query it
+    match run_migrations(&pool).await {
+        Ok(()) => tracing::info!("✅ Database migrations are up to date!"),
+        Err(err) => {
+            tracing::error!("❌ Failed to run database migrations: {:?}", err);
+            std::process::exit(1);
+        }
+    }
 
-async fn hello_world() -> &'static str {
-    "Hello, World1!"
-}
+    if let Some(Command::Seed) = cli.command {
+        match seed::seed_notes(&pool).await {
+            Ok(count) => tracing::info!(
+                "🌱 Seeded {} notes, log in as {} / {}",
+                count,
+                seed::SEED_EMAIL,
+                seed::SEED_PASSWORD
+            ),
+            Err(err) => {
+                tracing::error!("❌ Failed to seed the database: {}", err);
+                std::process::exit(1);
+            }
+        }
+        pool.close().await;
+        return;
+    }
 
-async fn post_hello_world() -> &'static str {
-    "Hello, World1 post!"
-}
+    // Migrations only run on the primary, the replica gets them through
+    // replication
+    let db_read = match &config.database_replica_url {
+        Some(url) => match connect_db(&config, url, "the replica").await {
+            Ok(replica) => {
+                tracing::info!("✅ Connection to the read replica is successful!");
+                replica
+            }
+            Err(err) => {
+                tracing::error!("❌ Failed to connect to the read replica: {:?}", err);
+                std::process::exit(1);
+            }
+        },
+        None => pool.clone(),
+    };
 
-// Two functions that return a router. This is very useful in larger applications
-// with lots of routes.
-fn pingpong() -> Router {
-    Router::new().route("/ping", get(|| async { "pong" }))
-}
+    let redis = match with_retries("redis", config.connect_retries, || {
+        open_redis(&config.redis_url, config.redis_max_connections)
+    })
+    .await
+    {
+        Ok(redis) => {
+            tracing::info!("✅ Connection to redis is successful!");
+            redis
+        }
+        Err(err) => {
+            tracing::error!("❌ Failed to connect to redis: {:?}", err);
+            std::process::exit(1);
+        }
+    };
 
-fn kingkong() -> Router {
-    async fn king() -> &'static str {
-        "Kong"
-    }
-    Router::new().route("/king", get(king))
-}
+    let engine = match templates::engine(&config.templates_dir) {
+        Ok(engine) => engine,
+        Err(err) => {
+            tracing::error!("❌ Failed to load templates: {}", err);
+            std::process::exit(1);
+        }
+    };
 
-// Path is an "Extractor". Extractors are used to extract data from the request.
-// .route("/greet/:name", get(greet_path))
-async fn greet_path(Path(name): Path<String>) -> String {
-    format!("Hello, {}!", name)
-}
+    let pool = Arc::new(AppState {
+        db: pool,
+        db_read,
+        redis,
+        poems: poems::PoemCache::new(config.poem_cache_ttl),
+        engine,
+        config,
+        metrics: metrics_handle,
+        note_events: note_events_channel(),
+        note_stats: NoteStats::new(),
+        numbers: numbers_channel(),
+    });
+    // Tells the background tasks to stop, see the shutdown below
+    let (shutdown_tx, mut shutdown_rx) = tokio::sync::watch::channel(false);
+    let relay = spawn_note_event_relay(pool.clone(), shutdown_rx.clone());
+    let app = build_app(pool.clone());
+    let listener = match tokio::net::TcpListener::bind(pool.config.bind_addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            tracing::error!("❌ Failed to bind {}: {}", pool.config.bind_addr, err);
+            std::process::exit(1);
+        }
+    };
+    // local_addr rather than the config so port 0 reports the assigned port
+    match listener.local_addr() {
+        Ok(addr) => tracing::info!("🚀 Listening on {}", addr),
+        Err(_) => tracing::info!("🚀 Listening on {}", pool.config.bind_addr),
+    }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
-struct QueryParameters {
-    salutation: Option<String>,
-    name: Option<String>,
-}
+    // On SIGINT/SIGTERM stop accepting connections, then give in-flight
+    // requests up to the shutdown timeout to finish before exiting.
+    let shutdown_timeout = pool.config.shutdown_timeout;
+    let stats_worker = spawn_note_stats_worker(pool.clone(), shutdown_rx.clone());
+    // Connect info gives ClientIp the peer address
+    let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+    let server = axum::serve(listener, make_service)
+        .with_graceful_shutdown(async move {
+            let _ = shutdown_rx.wait_for(|&stop| stop).await;
+        })
+        .into_future();
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => result.unwrap(),
+        _ = shutdown_signal() => {
+            tracing::info!("🛑 Shutting down, draining connections...");
+            let _ = shutdown_tx.send(true);
+            match tokio::time::timeout(shutdown_timeout, server).await {
+                Ok(result) => {
+                    result.unwrap();
+                    tracing::info!("✅ Server shut down gracefully");
+                }
+                Err(_) => tracing::error!(
+                    "❌ Shutdown timed out after {:?}, dropping remaining connections",
+                    shutdown_timeout
+                ),
+            }
+        }
+    }
 
-// Query is also an "Extractor".
-// .route("/greet", get(greet_query))
-async fn greet_query(Query(params): Query<QueryParameters>) -> String {
-    let salutation = params.salutation.unwrap_or_else(|| "Hello".to_string());
-    let name = params.name.unwrap_or_else(|| "World".to_string());
-    format!("{}, {}!", salutation, name)
-}
+    // Also stops the relay and the stats worker when the server exited on
+    // its own
+    let _ = shutdown_tx.send(true);
+    let _ = relay.await;
+    let _ = stats_worker.await;
 
-// Json is the third commonly used extractor.
-// .route("/greet", get(greet_query).post(greet_body))
-async fn greet_body(Json(params): Json<QueryParameters>) -> String {
-    let salutation = params.salutation.unwrap_or_else(|| "Hello".to_string());
-    let name = params.name.unwrap_or_else(|| "World".to_string());
-    format!("{}, {}!", salutation, name)
+    // Let MySQL see clean disconnects instead of dropped sockets
+    pool.db.close().await;
+    pool.db_read.close().await;
+    tracing::info!("✅ Database connections closed");
 }
 
-// NOTE: You can learn all details about extractor at
-// https://docs.rs/axum/latest/axum/extract/index.html#defining-custom-extractors
-
-#[derive(Serialize)]
-struct LookupResult {
-    number: i32,
-    found: bool,
+// Builds the Redis pool and checks out one connection so a bad URL or an
+// unreachable server is reported at startup rather than on first request.
+async fn open_redis(
+    url: &str,
+    max_size: usize,
+) -> Result<deadpool_redis::Pool, Box<dyn std::error::Error>> {
+    let mut cfg = deadpool_redis::Config::from_url(url);
+    cfg.pool = Some(deadpool_redis::PoolConfig::new(max_size));
+    let pool = cfg.create_pool(Some(deadpool_redis::Runtime::Tokio1))?;
+    redis::cmd("PING")
+        .query_async::<String>(&mut pool.get().await?)
+        .await?;
+    Ok(pool)
 }
 
-// A lot of types implement IntoResponse, including tuples.
-// Read more at https://docs.rs/axum/latest/axum/response/trait.IntoResponse.html
-// .route("/lookup", get(look_it_up))
-async fn look_it_up(Path(number): Path<i32>) -> impl IntoResponse {
-    // Let's say that only odd numbers are "found"
-    match number % 2 {
-        1 => (
-            StatusCode::OK,
-            Json(LookupResult {
-                number,
-                found: true,
-            }),
-        ),
-        _ => (
-            StatusCode::NOT_FOUND,
-            Json(LookupResult {
-                number,
-                found: false,
-            }),
-        ),
+// Runs `connect` until it succeeds or DB_CONNECT_RETRIES retries have
+// failed, waiting 1s, 2s, 4s, ... in between. When the app, the database
+// and Redis start together (docker compose) they may not accept
+// connections yet, and one failed attempt shouldn't kill the container.
+async fn with_retries<T, E, F, Fut>(what: &str, retries: u32, mut connect: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: std::fmt::Display,
+{
+    let attempts = retries + 1;
+    let mut attempt = 1;
+    let mut delay = CONNECT_BACKOFF;
+    loop {
+        match connect().await {
+            Ok(connected) => return Ok(connected),
+            Err(err) if attempt < attempts => {
+                tracing::warn!(
+                    "⏳ Connection attempt {}/{} to {} failed: {}, retrying in {:?}",
+                    attempt,
+                    attempts,
+                    what,
+                    err,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                delay *= 2;
+            }
+            Err(err) => return Err(err),
+        }
     }
 }
 
-// The easiest way to return different data types from a handler
-// is to convert them into Response, which implements IntoRespose.
-// .route("/random", get(return_something_random))
-async fn return_something_random() -> impl IntoResponse {
-    // Generate random number between 0 and 2 (including)
-    match rand::thread_rng().gen_range(0..3) {
-        0 => "Hello, World!".into_response(),
-        1 => StatusCode::NOT_IMPLEMENTED.into_response(),
-        _ => Json(42).into_response(),
-    }
+// The primary and the replica share the pool settings
+async fn connect_db(config: &Config, url: &str, what: &str) -> Result<MySqlPool, sqlx::Error> {
+    let options = connect_options(config, url)?;
+    // Dropping a timed out request stops waiting for its query, but MySQL
+    // keeps running it. The server aborts statements that outlive
+    // REQUEST_TIMEOUT_SECS instead, so they don't hold a connection.
+    let request_timeout = config.request_timeout;
+    let pool_options = MySqlPoolOptions::new()
+        .max_connections(config.db_max_connections)
+        .acquire_timeout(config.db_acquire_timeout)
+        .idle_timeout(config.db_idle_timeout)
+        .max_lifetime(config.db_max_lifetime)
+        .after_connect(move |conn, _meta| {
+            Box::pin(async move {
+                let version: String = sqlx::query_scalar("SELECT VERSION()")
+                    .fetch_one(&mut *conn)
+                    .await?;
+                sqlx::query(&statement_timeout_query(&version, request_timeout))
+                    .execute(conn)
+                    .await?;
+                Ok(())
+            })
+        });
+
+    with_retries(what, config.connect_retries, || {
+        pool_options.clone().connect_with(options.clone())
+    })
+    .await
 }
 
-fn numbers_management() -> Router<AppStateType> {
-    // State is another extractor. It can be used to extract shared state.
-    // Read more at https://docs.rs/axum/latest/axum/index.html#using-the-state-extractor
-    // .merge(numbers_management())
-    // .with_state(Arc::new(RwLock::new(AppState::default())))
-    async fn get_numbers(State(state): State<AppStateType>) -> impl IntoResponse {
-        Json(state.read().await.numbers.clone())
-    }
-
-    async fn add_number(
-        State(state): State<AppStateType>,
-        Json(new_number): Json<i32>,
-    ) -> impl IntoResponse {
-        let mut writable_state = state.write().await;
-        writable_state.numbers.push(new_number);
-        Json(writable_state.numbers.clone())
+// max_execution_time is MySQL's and only covers SELECTs. MariaDB, which
+// reports itself as e.g. "10.11.6-MariaDB", rejects it and has
+// max_statement_time in seconds instead, covering every statement.
+fn statement_timeout_query(server_version: &str, timeout: Duration) -> String {
+    if server_version.contains("MariaDB") {
+        format!("SET SESSION max_statement_time = {}", timeout.as_secs_f64())
+    } else {
+        format!("SET SESSION max_execution_time = {}", timeout.as_millis())
     }
+}
 
-    Router::new().route("/numbers", get(get_numbers).post(add_number))
+// Statements are logged through tracing under the `sqlx::query` target, so
+// they land in the request span: every one at DEBUG, and the ones slower
+// than SLOW_QUERY_MS at WARN.
+fn connect_options(config: &Config, url: &str) -> Result<MySqlConnectOptions, sqlx::Error> {
+    let options = url
+        .parse::<MySqlConnectOptions>()?
+        .log_statements(log::LevelFilter::Debug)
+        .log_slow_statements(log::LevelFilter::Warn, config.slow_query_threshold);
+    Ok(options)
 }
 
-fn poem() -> Router {
-    // Possible errors that can occur when reading poem from file.
-    // Note that this uses thiserror.
-    #[derive(Error, Debug)]
-    pub enum PoemError {
-        #[error("error accessing file")]
-        FileAccess(#[from] tokio::io::Error),
-        #[error("error parsing yaml")]
-        YamlParse(#[from] serde_yaml::Error),
+// Run pending migrations and log each one applied by this start.
+async fn run_migrations(pool: &MySqlPool) -> Result<(), sqlx::migrate::MigrateError> {
+    // The table doesn't exist yet on a fresh database, then nothing is applied
+    let applied_before: Vec<i64> = sqlx::query_scalar("SELECT version FROM _sqlx_migrations")
+        .fetch_all(pool)
+        .await
+        .unwrap_or_default();
+
+    let migrator = sqlx::migrate!();
+    migrator.run(pool).await?;
+
+    for migration in migrator.iter().filter(|migration| {
+        !migration.migration_type.is_down_migration()
+            && !applied_before.contains(&migration.version)
+    }) {
+        tracing::info!(
+            "✅ Applied migration {} {}",
+            migration.version,
+            migration.description
+        );
     }
+    Ok(())
+}
 
-    #[derive(Debug, Deserialize, Serialize)]
-    pub struct Poem {
-        pub title: String,
-        pub text: String,
-    }
+// Resolves when the process receives Ctrl+C or (on Unix) SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
 
-    // Let's write a helper method that reads a poem from a file.
-    async fn read_from_file(path: &str) -> Result<Poem, PoemError> {
-        let mut contents = String::new();
-        File::open(path)
-            .await?
-            .read_to_string(&mut contents)
-            .await?;
-        Ok(serde_yaml::from_str(&contents)?)
-    }
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
 
-    // Implement IntoResponse for our error type.
-    impl IntoResponse for PoemError {
-        fn into_response(self) -> Response {
-            let (status, error_message) = match self {
-                PoemError::FileAccess(ioe) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Error while accessing file: {ioe}"),
-                ),
-                PoemError::YamlParse(ye) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Error in YMAL file: {ye}"),
-                ),
-            };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-            let body = Json(error_message);
-            (status, body).into_response()
-        }
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
     }
+}
 
-    // Handler turning our poem into HTML.
-    async fn get_poem() -> Result<Html<String>, PoemError> {
-        let poem = read_from_file("poem.yaml").await?;
-        Ok(Html(format!(
-            r#"
-            <html>
-                <head>
-                    <title>{}</title>
-                </head>
-                <body>
-                    <h1>{}</h1>
-                    <pre>{}</pre>
-                </body>
-            </html>
-        "#,
-            poem.title, poem.title, poem.text
-        )))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mariadb_gets_its_own_statement_timeout() {
+        let timeout = Duration::from_millis(1500);
+        assert_eq!(
+            statement_timeout_query("8.0.36", timeout),
+            "SET SESSION max_execution_time = 1500"
+        );
+        assert_eq!(
+            statement_timeout_query("10.11.6-MariaDB-1:10.11.6+maria~ubu2204", timeout),
+            "SET SESSION max_statement_time = 1.5"
+        );
     }
-
-    Router::new().route("/poem", get(get_poem))
 }
-
-async fn health_check() -> impl IntoResponse {
-    const MESSAGE: &str = "API Services";
-    let json_response = serde_json::json!({
-        "status": "ok2",
-        "message": MESSAGE,
-    });
-    (StatusCode::OK, Json(json_response))
-}
\ No newline at end of file
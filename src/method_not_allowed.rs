@@ -0,0 +1,46 @@
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::error::AppError;
+
+// axum answers a known path with the wrong method with an empty 405. Swap
+// that for the usual JSON error body. The router adds the `Allow` header
+// once every layer has run, so it is not touched here, and an `Allow` set
+// by this middleware would stop the router from adding its own.
+pub async fn json_method_not_allowed(req: Request, next: Next) -> Response {
+    let response = next.run(req).await;
+    if response.status() == StatusCode::METHOD_NOT_ALLOWED
+        && !response.headers().contains_key(header::CONTENT_TYPE)
+    {
+        return AppError::MethodNotAllowed.into_response();
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn wrong_methods_get_json_and_the_allowed_methods() {
+        let app = Router::new()
+            .route("/thing", get(|| async { "thing" }).put(|| async { "put" }))
+            .layer(middleware::from_fn(json_method_not_allowed));
+
+        let response = app
+            .oneshot(Request::post("/thing").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        let allow = response.headers()[header::ALLOW].to_str().unwrap();
+        assert!(allow.contains("GET") && allow.contains("PUT"), "{allow}");
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    }
+}
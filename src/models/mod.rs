@@ -1,2 +1,3 @@
 pub mod model;
+pub mod note;
 pub mod schema;
\ No newline at end of file
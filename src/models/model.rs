@@ -1,25 +1,11 @@
-use serde::{Deserialize, Serialize};
-
-// For sqlx
-#[derive(Debug, Deserialize, Serialize, sqlx::FromRow)]
-#[allow(non_snake_case)]
-pub struct NoteModel {
+// For sqlx, the full users row even though login only reads a few columns
+#[allow(dead_code)]
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserModel {
     pub id: String,
-    pub title: String,
-    pub content: String,
-    pub is_published: i8,
+    pub email: String,
+    pub password_hash: String,
+    pub is_admin: i8,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
 }
-
-// For json response
-#[derive(Debug, Deserialize, Serialize)]
-#[allow(non_snake_case)]
-pub struct NoteModelResponse {
-    pub id: String,
-    pub title: String,
-    pub content: String,
-    pub is_published: bool,
-    pub created_at: chrono::DateTime<chrono::Utc>,
-    pub updated_at: chrono::DateTime<chrono::Utc>,
-}
\ No newline at end of file
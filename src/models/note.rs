@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// A row of the notes table, read with `sqlx::query_as::<_, NoteModel>`
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, ToSchema)]
+pub struct NoteModel {
+    pub id: String,
+    // Owner, NULL only for notes created before notes were scoped to users
+    pub user_id: Option<String>,
+    pub title: String,
+    pub content: String,
+    pub is_published: i8,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // Set when the note is soft-deleted
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+// For json response
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct NoteModelResponse {
+    pub id: String,
+    pub title: String,
+    pub content: String,
+    pub is_published: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // Only present on soft-deleted notes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    // Only loaded for single-note responses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+// A row of note_history, served as is by GET /api/notes/:id/history
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct NoteHistoryModel {
+    pub id: u64,
+    // "create", "edit", "delete" or "restore"
+    pub action: String,
+    // Who made the change, from the token's `sub`
+    pub user_id: String,
+    // The note before the change, null for a create
+    pub previous_title: Option<String>,
+    pub previous_content: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
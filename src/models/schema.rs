@@ -1,31 +1,173 @@
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+// Limits checked before a note reaches the database
+pub const MAX_TITLE_CHARS: usize = 255;
+pub const MAX_CONTENT_BYTES: usize = 64 * 1024;
+// Matches the tags.name column
+pub const MAX_TAG_CHARS: usize = 64;
 
 // List
-#[derive(Deserialize, Debug, Default)]
+// Signed so that out-of-range values reach the handler and get clamped
+// instead of failing deserialization.
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct FilterOptions {
-    pub page: Option<usize>,
-    pub limit: Option<usize>,
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+    pub q: Option<String>,
+    pub tag: Option<String>,
+    // created_at (the default), updated_at or title
+    pub sort: Option<String>,
+    // asc or desc, by default newest first for dates and A-Z for titles
+    pub order: Option<String>,
+    // Also list soft-deleted notes
+    pub include_deleted: Option<bool>,
+    // Add `total`, which costs an extra COUNT(*) query
+    pub count: Option<bool>,
+}
+
+// Clear all notes, the request is refused unless confirm=true
+#[derive(Deserialize, Debug, Default)]
+pub struct ClearNotesOptions {
+    pub confirm: Option<bool>,
+}
+
+// Export, csv (the default) or json for newline-delimited JSON
+#[derive(Deserialize, Debug, Default)]
+pub struct ExportOptions {
+    pub format: Option<String>,
 }
 
-// Read/Delete
-#[derive(Deserialize, Debug)]
-pub struct ParamOptions {
-    pub id: String,
+// Search, paged like the list but with a smaller largest page
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchOptions {
+    pub q: Option<String>,
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
 }
 
 // Create
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Validate, ToSchema)]
 pub struct CreateNoteSchema {
+    #[validate(custom(function = "validate_title"))]
     pub title: String,
+    #[validate(custom(function = "validate_content"))]
     pub content: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_published: Option<bool>,
+    // Attached in the same transaction as the insert
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Option<Vec<String>>,
 }
 
-// Update
-#[derive(Serialize, Deserialize, Debug)]
+// Update (fields that are left out are not checked)
+#[derive(Serialize, Deserialize, Debug, Validate, ToSchema)]
 pub struct UpdateNoteSchema {
+    #[validate(custom(function = "validate_title"))]
     pub title: Option<String>,
+    #[validate(custom(function = "validate_content"))]
     pub content: Option<String>,
     pub is_published: Option<bool>,
-}
\ No newline at end of file
+}
+
+impl UpdateNoteSchema {
+    // `{}`, nothing to change
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.content.is_none() && self.is_published.is_none()
+    }
+}
+
+// Surrounding whitespace doesn't count towards the title length
+fn validate_title(title: &str) -> Result<(), ValidationError> {
+    let length = title.trim().chars().count();
+    if length == 0 {
+        return Err(ValidationError::new("length").with_message("must not be empty".into()));
+    }
+    if length > MAX_TITLE_CHARS {
+        return Err(ValidationError::new("length")
+            .with_message(format!("must be at most {} characters", MAX_TITLE_CHARS).into()));
+    }
+    Ok(())
+}
+
+// Measured in bytes since that is what ends up in the TEXT column
+fn validate_content(content: &str) -> Result<(), ValidationError> {
+    if content.len() > MAX_CONTENT_BYTES {
+        return Err(ValidationError::new("length")
+            .with_message(format!("must be at most {} bytes", MAX_CONTENT_BYTES).into()));
+    }
+    Ok(())
+}
+
+fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+    for tag in tags {
+        let length = tag.trim().chars().count();
+        if length == 0 || length > MAX_TAG_CHARS {
+            return Err(ValidationError::new("length").with_message(
+                format!("each tag must be 1 to {} characters", MAX_TAG_CHARS).into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Attach tag
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TagSchema {
+    pub tag: String,
+}
+
+// Login (no Debug, so the password can't end up in a log line)
+#[derive(Deserialize, ToSchema)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+// Register
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct RegisterRequest {
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: String,
+    #[validate(length(min = 8, message = "must be at least 8 characters"))]
+    pub password: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(title: &str, content: String) -> CreateNoteSchema {
+        CreateNoteSchema {
+            title: title.to_string(),
+            content,
+            is_published: None,
+            tags: None,
+        }
+    }
+
+    #[test]
+    fn notes_need_a_title_and_bounded_content() {
+        let longest = note("title", "x".repeat(MAX_CONTENT_BYTES));
+        assert!(longest.validate().is_ok());
+
+        let errors = note("  ", "x".repeat(MAX_CONTENT_BYTES + 1))
+            .validate()
+            .unwrap_err();
+        let fields = errors.field_errors();
+        assert!(fields.contains_key("title"));
+        assert!(fields.contains_key("content"));
+
+        let edit = UpdateNoteSchema {
+            title: Some("x".repeat(MAX_TITLE_CHARS + 1)),
+            content: None,
+            is_published: None,
+        };
+        let errors = edit.validate().unwrap_err();
+        assert!(errors.field_errors().contains_key("title"));
+    }
+}
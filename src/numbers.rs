@@ -0,0 +1,208 @@
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, State},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    routing::{delete, get},
+    Json, Router,
+};
+use futures_util::{stream, Stream, StreamExt};
+use redis::AsyncCommands;
+use tokio::sync::watch;
+
+use crate::{error::AppError, AppState};
+
+// Redis list holding the numbers, shared by every server instance.
+const NUMBERS_KEY: &str = "numbers";
+
+// Comment line sent on an idle /numbers/stream so proxies keep it open
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// The list after each change made through this instance, for
+// AppState::numbers. Changes made by other replicas aren't seen here.
+pub fn numbers_channel() -> watch::Sender<Vec<i32>> {
+    watch::Sender::new(Vec::new())
+}
+
+pub fn numbers_management(app_state: Arc<AppState>) -> Router {
+    // State is another extractor. It can be used to extract shared state.
+    // Read more at https://docs.rs/axum/latest/axum/index.html#using-the-state-extractor
+    // The numbers live in Redis, so they survive restarts and are shared
+    // across replicas.
+    async fn get_numbers(
+        State(state): State<Arc<AppState>>,
+    ) -> Result<impl IntoResponse, AppError> {
+        let mut conn = state.redis_conn().await?;
+        let stored: Vec<String> = conn.lrange(NUMBERS_KEY, 0, -1).await?;
+        Ok(Json(parse_numbers(stored)))
+    }
+
+    async fn add_number(
+        State(state): State<Arc<AppState>>,
+        Json(new_number): Json<i32>,
+    ) -> Result<impl IntoResponse, AppError> {
+        let mut conn = state.redis_conn().await?;
+        // Push and read back in one MULTI/EXEC so the returned list is
+        // exactly the state right after our push.
+        let (stored,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .rpush(NUMBERS_KEY, new_number)
+            .ignore()
+            .lrange(NUMBERS_KEY, 0, -1)
+            .query_async(&mut conn)
+            .await?;
+        Ok(Json(publish(&state, parse_numbers(stored))))
+    }
+
+    async fn replace_numbers(
+        State(state): State<Arc<AppState>>,
+        Json(numbers): Json<Vec<i32>>,
+    ) -> Result<impl IntoResponse, AppError> {
+        let mut conn = state.redis_conn().await?;
+        let (stored,): (Vec<String>,) = replace_pipeline(&numbers).query_async(&mut conn).await?;
+        Ok(Json(publish(&state, parse_numbers(stored))))
+    }
+
+    async fn delete_number(
+        State(state): State<Arc<AppState>>,
+        Path(index): Path<usize>,
+    ) -> Result<impl IntoResponse, AppError> {
+        let mut conn = state.redis_conn().await?;
+        // Redis lists can't drop an element by position, so the list is read,
+        // edited and written back. WATCH makes the EXEC fail if another client
+        // changed the list in between, then we start over.
+        loop {
+            let _: () = redis::cmd("WATCH")
+                .arg(NUMBERS_KEY)
+                .query_async(&mut conn)
+                .await?;
+            let stored: Vec<String> = conn.lrange(NUMBERS_KEY, 0, -1).await?;
+            let Some(numbers) = remove_at(parse_numbers(stored), index) else {
+                // the connection goes back to the pool, don't leave it watching
+                let _: () = redis::cmd("UNWATCH").query_async(&mut conn).await?;
+                return Err(AppError::NotFound(format!("No number at index {}", index)));
+            };
+
+            let result: Option<(Vec<String>,)> =
+                replace_pipeline(&numbers).query_async(&mut conn).await?;
+            if let Some((stored,)) = result {
+                return Ok(Json(publish(&state, parse_numbers(stored))));
+            }
+        }
+    }
+
+    // Server-Sent Events, one `data:` line with the whole list as JSON,
+    // first the current list and then again after every change
+    async fn stream_numbers(
+        State(state): State<Arc<AppState>>,
+    ) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+        // Subscribe before reading Redis so a change in between isn't lost
+        let updates = state.numbers.subscribe();
+        let mut conn = state.redis_conn().await?;
+        let stored: Vec<String> = conn.lrange(NUMBERS_KEY, 0, -1).await?;
+        drop(conn);
+
+        let changes = stream::unfold(updates, |mut updates| async move {
+            updates.changed().await.ok()?;
+            let numbers = updates.borrow_and_update().clone();
+            Some((numbers, updates))
+        });
+        let events = stream::once(async move { parse_numbers(stored) })
+            .chain(changes)
+            .map(|numbers| Ok(numbers_event(&numbers)));
+        Ok(Sse::new(events).keep_alive(
+            KeepAlive::new()
+                .interval(KEEP_ALIVE_INTERVAL)
+                .text("keep-alive"),
+        ))
+    }
+
+    Router::new()
+        .route(
+            "/numbers",
+            get(get_numbers).post(add_number).put(replace_numbers),
+        )
+        .route("/numbers/stream", get(stream_numbers))
+        .route("/numbers/:index", delete(delete_number))
+        .with_state(app_state)
+}
+
+// Hands the new list to /numbers/stream subscribers and returns it
+fn publish(state: &AppState, numbers: Vec<i32>) -> Vec<i32> {
+    state.numbers.send_replace(numbers.clone());
+    numbers
+}
+
+fn numbers_event(numbers: &[i32]) -> Event {
+    // Serializing a slice of integers can't fail
+    Event::default().data(serde_json::to_string(numbers).unwrap_or_default())
+}
+
+// MULTI/EXEC swapping the whole list for `numbers` and reading it back
+fn replace_pipeline(numbers: &[i32]) -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    pipe.atomic().del(NUMBERS_KEY).ignore();
+    // RPUSH needs at least one value, an empty list is just the DEL
+    if !numbers.is_empty() {
+        pipe.rpush(NUMBERS_KEY, numbers).ignore();
+    }
+    pipe.lrange(NUMBERS_KEY, 0, -1);
+    pipe
+}
+
+// None when `index` is past the end of the list
+fn remove_at(mut numbers: Vec<i32>, index: usize) -> Option<Vec<i32>> {
+    if index >= numbers.len() {
+        return None;
+    }
+    numbers.remove(index);
+    Some(numbers)
+}
+
+// Redis stores the numbers as strings. Anything that isn't an i32, say
+// written to the key by hand, is skipped rather than failing the request.
+fn parse_numbers(stored: Vec<String>) -> Vec<i32> {
+    stored
+        .into_iter()
+        .filter_map(|value| match value.parse() {
+            Ok(number) => Some(number),
+            Err(_) => {
+                tracing::warn!(value = %value, "skipping non-numeric entry in {}", NUMBERS_KEY);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_at_drops_the_first_and_last_element() {
+        assert_eq!(remove_at(vec![1, 2, 3], 0), Some(vec![2, 3]));
+        assert_eq!(remove_at(vec![1, 2, 3], 2), Some(vec![1, 2]));
+    }
+
+    #[test]
+    fn remove_at_rejects_an_index_past_the_end() {
+        assert_eq!(remove_at(vec![1, 2, 3], 3), None);
+        assert_eq!(remove_at(vec![1, 2, 3], usize::MAX), None);
+    }
+
+    #[test]
+    fn remove_at_rejects_any_index_on_an_empty_list() {
+        assert_eq!(remove_at(Vec::new(), 0), None);
+    }
+
+    #[tokio::test]
+    async fn events_carry_the_whole_list_as_json() {
+        let events = stream::iter([Ok::<_, Infallible>(numbers_event(&[1, 2, 3]))]);
+        let body = Sse::new(events).into_response().into_body();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"data: [1,2,3]\n\n");
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path as FsPath, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header::VARY, HeaderMap},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{app::prefers_json, error::AppError, templates, AppState};
+
+// Served by the old /poem route, POEMS_DIR/poem.yaml
+const DEFAULT_POEM: &str = "poem";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Poem {
+    pub title: String,
+    pub text: String,
+}
+
+// One entry of GET /poems, the slug is the file name without `.yaml`
+#[derive(Debug, Serialize)]
+pub struct PoemSummary {
+    pub title: String,
+    pub slug: String,
+}
+
+// A poem in the format the Accept header asked for, rendered HTML unless
+// JSON is preferred
+pub enum PoemResponse {
+    Html(String),
+    Json(Arc<Poem>),
+}
+
+impl IntoResponse for PoemResponse {
+    fn into_response(self) -> Response {
+        // Caches must not hand the HTML version to a JSON client
+        let vary = [(VARY, "accept")];
+        match self {
+            PoemResponse::Html(html) => (vary, Html(html)).into_response(),
+            PoemResponse::Json(poem) => (vary, Json(poem)).into_response(),
+        }
+    }
+}
+
+// Parsed poems by slug, so repeated requests skip the file read and the
+// YAML parse. Entries are dropped lazily once they are older than
+// POEM_CACHE_TTL_SECS, edits to a file show up after at most that long.
+pub struct PoemCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<String, (Instant, Arc<Poem>)>>,
+}
+
+impl PoemCache {
+    pub fn new(ttl: Duration) -> Self {
+        PoemCache {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, slug: &str) -> Option<Arc<Poem>> {
+        let entries = self.entries.read().unwrap_or_else(|err| err.into_inner());
+        match entries.get(slug) {
+            Some((loaded, poem)) if loaded.elapsed() < self.ttl => Some(poem.clone()),
+            _ => None,
+        }
+    }
+
+    fn insert(&self, slug: &str, poem: Arc<Poem>) {
+        let mut entries = self.entries.write().unwrap_or_else(|err| err.into_inner());
+        // Stale entries of other slugs go too, so removed files don't linger
+        entries.retain(|_, (loaded, _)| loaded.elapsed() < self.ttl);
+        entries.insert(slug.to_string(), (Instant::now(), poem));
+    }
+}
+
+pub fn poems(app_state: Arc<AppState>) -> Router {
+    // GET /poems, every `.yaml` file in POEMS_DIR sorted by slug
+    async fn list_poems(
+        State(state): State<Arc<AppState>>,
+    ) -> Result<Json<Vec<PoemSummary>>, AppError> {
+        let dir = &state.config.poems_dir;
+        let mut entries = fs::read_dir(dir).await.map_err(|err| match err.kind() {
+            ErrorKind::NotFound => AppError::NotFound("No poems directory".to_string()),
+            _ => err.into(),
+        })?;
+
+        let mut poems = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let Some(slug) = poem_slug(&path) else {
+                continue;
+            };
+            // One broken file shouldn't hide all the others
+            match read_from_file(&path).await {
+                Ok(poem) => poems.push(PoemSummary {
+                    title: poem.title,
+                    slug,
+                }),
+                Err(err) => tracing::warn!(path = %path.display(), error = %err, "skipping poem"),
+            }
+        }
+        poems.sort_by(|a, b| a.slug.cmp(&b.slug));
+        Ok(Json(poems))
+    }
+
+    // GET /poems/:slug and GET /poem/:name
+    async fn get_poem(
+        State(state): State<Arc<AppState>>,
+        Path(slug): Path<String>,
+        headers: HeaderMap,
+    ) -> Result<PoemResponse, AppError> {
+        render_poem(&state, &slug, &headers).await
+    }
+
+    // GET /poem, kept for clients that predate POEMS_DIR
+    async fn get_default_poem(
+        State(state): State<Arc<AppState>>,
+        headers: HeaderMap,
+    ) -> Result<PoemResponse, AppError> {
+        render_poem(&state, DEFAULT_POEM, &headers).await
+    }
+
+    Router::new()
+        .route("/poem", get(get_default_poem))
+        .route("/poem/:name", get(get_poem))
+        .route("/poems", get(list_poems))
+        .route("/poems/:slug", get(get_poem))
+        .with_state(app_state)
+}
+
+// Handler turning our poem into HTML, Handlebars escapes the title and text.
+// API clients sending `Accept: application/json` get the Poem itself.
+async fn render_poem(
+    state: &AppState,
+    slug: &str,
+    headers: &HeaderMap,
+) -> Result<PoemResponse, AppError> {
+    let poem = match state.poems.get(slug) {
+        Some(poem) => poem,
+        None => {
+            let poem = Arc::new(find_poem(&state.config.poems_dir, slug).await?);
+            state.poems.insert(slug, poem.clone());
+            poem
+        }
+    };
+    Ok(match prefers_json(headers) {
+        true => PoemResponse::Json(poem),
+        false => PoemResponse::Html(templates::render(&state.engine, "poem", &poem)?),
+    })
+}
+
+// A missing file (or directory) is a 404, any other I/O error stays a 500
+async fn find_poem(dir: &FsPath, slug: &str) -> Result<Poem, AppError> {
+    let path = poem_path(dir, slug)?;
+    match read_from_file(&path).await {
+        Err(AppError::FileAccess(err)) if err.kind() == ErrorKind::NotFound => {
+            Err(AppError::NotFound(format!("Poem {} not found", slug)))
+        }
+        result => result,
+    }
+}
+
+// The slug must be a single plain file name, so `../`, absolute paths and
+// nested directories can't reach outside POEMS_DIR. Path params arrive
+// percent-decoded, `..%2F` is caught here too.
+fn poem_path(dir: &FsPath, slug: &str) -> Result<PathBuf, AppError> {
+    if slug.is_empty() || slug.starts_with('.') || slug.contains(['/', '\\']) {
+        return Err(AppError::BadRequest(format!(
+            "Invalid poem name {:?}",
+            slug
+        )));
+    }
+    Ok(dir.join(format!("{}.yaml", slug)))
+}
+
+fn poem_slug(path: &FsPath) -> Option<String> {
+    if path.extension()? != "yaml" {
+        return None;
+    }
+    Some(path.file_stem()?.to_str()?.to_string())
+}
+
+// Let's write a helper method that reads a poem from a file.
+// File and YAML errors convert into AppError via thiserror's #[from]. Bytes
+// that aren't UTF-8 are checked separately, read_to_string would report them
+// as a plain I/O error.
+async fn read_from_file(path: &FsPath) -> Result<Poem, AppError> {
+    let bytes = fs::read(path).await?;
+    let contents = String::from_utf8(bytes).map_err(|source| AppError::Encoding {
+        path: path.display().to_string(),
+        source,
+    })?;
+    Ok(serde_yaml::from_str(&contents)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poem() -> Arc<Poem> {
+        Arc::new(Poem {
+            title: "title".to_string(),
+            text: "text".to_string(),
+        })
+    }
+
+    #[test]
+    fn cached_poems_are_served_until_the_ttl_passes() {
+        let cache = PoemCache::new(Duration::from_secs(60));
+        cache.insert("poem", poem());
+        assert!(cache.get("poem").is_some());
+        assert!(cache.get("other").is_none());
+
+        let expired = PoemCache::new(Duration::ZERO);
+        expired.insert("poem", poem());
+        assert!(expired.get("poem").is_none());
+    }
+
+    #[test]
+    fn plain_names_resolve_inside_the_directory() {
+        let path = poem_path(FsPath::new("poems"), "how-cool").unwrap();
+        assert_eq!(path, FsPath::new("poems/how-cool.yaml"));
+    }
+
+    #[test]
+    fn names_that_could_escape_the_directory_are_rejected() {
+        for slug in [
+            "",
+            "..",
+            "../secret",
+            "a/b",
+            "/etc/passwd",
+            "a\\..\\b",
+            ".hidden",
+        ] {
+            assert!(
+                matches!(
+                    poem_path(FsPath::new("poems"), slug),
+                    Err(AppError::BadRequest(_))
+                ),
+                "{slug:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn files_that_are_not_utf8_say_so() {
+        let path = std::env::temp_dir().join(format!("poem-{}.yaml", std::process::id()));
+        std::fs::write(&path, b"title: caf\xe9\ntext: text\n").unwrap();
+        let result = read_from_file(&path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        let err = result.unwrap_err();
+        assert!(matches!(err, AppError::Encoding { .. }), "{err:?}");
+        assert!(err.to_string().contains("not valid UTF-8"), "{err}");
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{error::AppError, extractors::ClientIp, AppState};
+
+// Length of a rate limit window. Counters expire together with the window.
+const WINDOW_SECS: u64 = 60;
+
+// Probes and scrapes poll on their own schedule and must not wait on Redis
+// or eat into the budget of the client they share an IP with.
+const EXEMPT_PATHS: &[&str] = &[
+    "/livez",
+    "/readyz",
+    "/healthcheck",
+    "/healthcheck/live",
+    "/healthcheck/ready",
+    "/metrics",
+];
+
+// Fixed-window limiter keyed by client IP. Each IP may make
+// RATE_LIMIT_PER_MINUTE requests per window; Redis trouble lets requests
+// through rather than taking the whole API down with it.
+// .layer(middleware::from_fn_with_state(pool.clone(), rate_limit))
+pub async fn rate_limit(
+    State(data): State<Arc<AppState>>,
+    ClientIp(ip): ClientIp,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if is_exempt(req.uri().path()) {
+        return Ok(next.run(req).await);
+    }
+
+    let ip = ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+    let now = chrono::Utc::now().timestamp() as u64;
+    let window = now / WINDOW_SECS;
+
+    match count_hit(&data, &ip, window).await {
+        Ok(hits) if hits > data.config.rate_limit_per_minute => {
+            return Err(AppError::RateLimited(WINDOW_SECS - now % WINDOW_SECS));
+        }
+        Ok(_) => {}
+        Err(err) => tracing::warn!(error = %err, "rate limiter unavailable, allowing request"),
+    }
+
+    Ok(next.run(req).await)
+}
+
+fn is_exempt(path: &str) -> bool {
+    EXEMPT_PATHS.contains(&path)
+}
+
+// Increment and return this window's counter for the client
+async fn count_hit(data: &AppState, ip: &str, window: u64) -> Result<u64, AppError> {
+    let key = format!("rate_limit:{}:{}", ip, window);
+    let mut conn = data.redis_conn().await?;
+    let (hits,): (u64,) = redis::pipe()
+        .atomic()
+        .incr(&key, 1)
+        .expire(&key, WINDOW_SECS as i64)
+        .ignore()
+        .query_async(&mut conn)
+        .await?;
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_and_metrics_are_not_limited() {
+        for path in ["/livez", "/healthcheck/live", "/readyz", "/metrics"] {
+            assert!(is_exempt(path), "{path}");
+        }
+        for path in ["/", "/api/notes", "/livez/extra", "/healthcheck/"] {
+            assert!(!is_exempt(path), "{path}");
+        }
+    }
+}
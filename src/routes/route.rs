@@ -1,27 +1,305 @@
 use std::sync::Arc;
 
 use axum::{
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{delete, get, post},
     Router,
 };
 
 use crate::{
+    auth::{require_admin, require_auth, require_socket_auth},
+    handlers::export::export_notes_handler,
     handlers::handler::{
-        create_note_handler, delete_note_handler, edit_note_handler, get_note_handler,
-        note_list_handler,
+        attach_tag_handler, batch_create_notes_handler, bulk_create_notes_handler,
+        clear_notes_handler, create_note_handler, delete_note_handler, detach_tag_handler,
+        duplicate_note_handler, edit_note_handler, get_note_handler, liveness_handler,
+        login_handler, note_history_handler, note_list_handler, readiness_handler,
+        register_handler, restore_note_handler, search_notes_handler,
     },
+    handlers::import::import_notes_handler,
+    handlers::live::{live_notes_handler, ws_notes_handler},
+    handlers::stats::note_stats_handler,
     AppState,
 };
 
 pub fn create_router(app_state: Arc<AppState>) -> Router {
-    Router::new()
-        .route("/api/notes", post(create_note_handler))
-        .route("/api/notes", get(note_list_handler))
+    // Notes belong to users, so every notes route requires a valid bearer token
+    let protected = Router::new()
+        .route(
+            "/api/notes",
+            get(note_list_handler)
+                .post(create_note_handler)
+                // Wipes every user's notes, so admins only
+                .merge(delete(clear_notes_handler).route_layer(middleware::from_fn(require_admin))),
+        )
+        .route("/api/notes/bulk", post(bulk_create_notes_handler))
+        .route("/api/notes/batch", post(batch_create_notes_handler))
+        .route("/api/notes/search", get(search_notes_handler))
+        .route("/api/notes/export", get(export_notes_handler))
+        // Counts every user's changes, so admins only
+        .route(
+            "/api/notes/stats",
+            get(note_stats_handler).route_layer(middleware::from_fn(require_admin)),
+        )
+        // CSV files may be larger than the usual MAX_BODY_BYTES
+        .route(
+            "/api/notes/import",
+            post(import_notes_handler)
+                .layer(DefaultBodyLimit::max(app_state.config.max_import_bytes)),
+        )
         .route(
             "/api/notes/:id",
             get(get_note_handler)
                 .patch(edit_note_handler)
                 .delete(delete_note_handler),
         )
+        .route("/api/notes/:id/restore", post(restore_note_handler))
+        .route("/api/notes/:id/duplicate", post(duplicate_note_handler))
+        .route("/api/notes/:id/history", get(note_history_handler))
+        .route("/api/notes/:id/tags", post(attach_tag_handler))
+        .route("/api/notes/:id/tags/:tag", delete(detach_tag_handler))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            require_auth,
+        ));
+
+    // Same check, but the token may also be in the query string
+    let sockets = Router::new()
+        .route("/api/notes/live", get(live_notes_handler))
+        .route("/ws/notes", get(ws_notes_handler))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            require_socket_auth,
+        ));
+
+    Router::new()
+        .route("/livez", get(liveness_handler))
+        .route("/readyz", get(readiness_handler))
+        .route("/healthcheck/live", get(liveness_handler))
+        .route("/healthcheck/ready", get(readiness_handler))
+        // Kept for existing monitors, same checks as /readyz
+        .route("/healthcheck", get(readiness_handler))
+        .route("/api/auth/login", post(login_handler))
+        .route("/api/auth/register", post(register_handler))
+        .merge(protected)
+        .merge(sockets)
         .with_state(app_state)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+    };
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use sqlx::mysql::MySqlPoolOptions;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{config::Config, poems::PoemCache, templates};
+
+    // Neither pool connects until it is used, and the requests below are all
+    // answered by the router or require_auth before a handler runs.
+    fn app() -> Router {
+        let config = Config {
+            database_url: "mysql://localhost/notes".to_string(),
+            database_replica_url: None,
+            db_max_connections: 1,
+            connect_retries: 0,
+            db_acquire_timeout: Duration::from_secs(1),
+            db_idle_timeout: Duration::from_secs(1),
+            db_max_lifetime: Duration::from_secs(1),
+            redis_url: "redis://127.0.0.1/".to_string(),
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            jwt_secret: "secret".to_string(),
+            redis_max_connections: 1,
+            rate_limit_per_minute: 60,
+            trust_proxy: false,
+            shutdown_timeout: Duration::from_secs(1),
+            note_cache_ttl: Duration::from_secs(1),
+            cors_allowed_origins: None,
+            cors_allow_credentials: false,
+            max_body_bytes: 1024,
+            max_import_bytes: 4096,
+            max_batch: 10,
+            request_timeout: Duration::from_secs(1),
+            slow_query_threshold: Duration::from_secs(1),
+            poems_dir: "./poems".into(),
+            poem_cache_ttl: Duration::from_secs(1),
+            templates_dir: "./templates".into(),
+            static_dir: "./static".into(),
+            random_seed: None,
+        };
+        let db = MySqlPoolOptions::new()
+            .connect_lazy(&config.database_url)
+            .unwrap();
+        let redis = deadpool_redis::Config::from_url(&config.redis_url)
+            .create_pool(Some(deadpool_redis::Runtime::Tokio1))
+            .unwrap();
+        create_router(Arc::new(AppState {
+            db: db.clone(),
+            db_read: db,
+            redis,
+            poems: PoemCache::new(config.poem_cache_ttl),
+            engine: templates::engine(&config.templates_dir).unwrap(),
+            config,
+            metrics: PrometheusBuilder::new().build_recorder().handle(),
+            note_events: crate::note_events_channel(),
+            note_stats: crate::NoteStats::new(),
+            numbers: crate::numbers_channel(),
+        }))
+    }
+
+    async fn get(path: &str) -> (StatusCode, String) {
+        send(Request::builder().uri(path).body(Body::empty()).unwrap()).await
+    }
+
+    async fn send(request: Request<Body>) -> (StatusCode, String) {
+        let response = app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    // Every /api/notes path is a static route of the protected router, so it
+    // reaches require_auth instead of a catch-all under /api.
+    #[tokio::test]
+    async fn notes_routes_are_matched_before_anything_dynamic() {
+        for path in [
+            "/api/notes",
+            "/api/notes/search",
+            "/api/notes/export",
+            "/api/notes/stats",
+            "/api/notes/some-id",
+            "/api/notes/some-id/history",
+            "/ws/notes",
+        ] {
+            let (status, body) = get(path).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{path}");
+            assert!(body.contains("Missing bearer token"), "{path}: {body}");
+        }
+    }
+
+    #[tokio::test]
+    async fn liveness_does_not_touch_dependencies() {
+        for path in ["/livez", "/healthcheck/live"] {
+            let (status, body) = get(path).await;
+            assert_eq!(status, StatusCode::OK, "{path}");
+            assert!(body.contains("ok"), "{path}: {body}");
+        }
+    }
+
+    // Browsers can't send an Authorization header with a WebSocket upgrade.
+    // A plain GET isn't an upgrade, so a token that passes gets a 400 from
+    // the WebSocketUpgrade extractor instead of a 401.
+    #[tokio::test]
+    async fn sockets_take_the_token_from_the_query_string() {
+        let (token, _) = crate::auth::issue_token("user", false, "secret").unwrap();
+        for path in ["/ws/notes", "/api/notes/live"] {
+            let (status, body) = get(&format!("{path}?access_token={token}")).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{path}: {body}");
+            let (status, body) = get(&format!("{path}?access_token=forged")).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{path}");
+            assert!(body.contains("Invalid token"), "{path}: {body}");
+        }
+
+        // The other notes routes still want the header
+        let (status, _) = get(&format!("/api/notes?access_token={token}")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn note_stats_are_for_admins_only() {
+        for (admin, expected) in [(false, StatusCode::FORBIDDEN), (true, StatusCode::OK)] {
+            let (token, _) = crate::auth::issue_token("user", admin, "secret").unwrap();
+            let request = Request::builder()
+                .uri("/api/notes/stats")
+                .header("authorization", format!("Bearer {token}"))
+                .body(Body::empty())
+                .unwrap();
+            assert_eq!(send(request).await.0, expected, "admin: {admin}");
+        }
+    }
+
+    async fn clear_notes(admin: bool, query: &str) -> StatusCode {
+        let (token, _) = crate::auth::issue_token("user", admin, "secret").unwrap();
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/api/notes{query}"))
+            .header("authorization", format!("Bearer {token}"))
+            .body(Body::empty())
+            .unwrap();
+        send(request).await.0
+    }
+
+    #[tokio::test]
+    async fn clearing_notes_needs_an_admin_and_confirmation() {
+        assert_eq!(
+            clear_notes(false, "?confirm=true").await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(clear_notes(true, "").await, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            clear_notes(true, "?confirm=no").await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn batches_over_max_batch_are_rejected_before_any_query() {
+        let (token, _) = crate::auth::issue_token("user", false, "secret").unwrap();
+        let note = r#"{"title":"t","content":"c"}"#;
+        let notes = format!("[{}]", [note; 11].join(","));
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/notes/batch")
+            .header("authorization", format!("Bearer {token}"))
+            .header("content-type", "application/json")
+            .body(Body::from(notes))
+            .unwrap();
+        let (status, body) = send(request).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body.contains("over the limit of 10"), "{body}");
+    }
+
+    async fn import(csv: &str) -> (StatusCode, String) {
+        let (token, _) = crate::auth::issue_token("user", false, "secret").unwrap();
+        let body = format!(
+            "--boundary\r\n\
+             Content-Disposition: form-data; name=\"file\"; filename=\"notes.csv\"\r\n\
+             Content-Type: text/csv\r\n\r\n\
+             {csv}\r\n\
+             --boundary--\r\n"
+        );
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/notes/import")
+            .header("authorization", format!("Bearer {token}"))
+            .header("content-type", "multipart/form-data; boundary=boundary")
+            .body(Body::from(body))
+            .unwrap();
+        send(request).await
+    }
+
+    // Both are refused while reading the upload, before any query
+    #[tokio::test]
+    async fn imports_need_the_columns_and_a_bounded_file() {
+        let (status, body) = import("name,text\na,b").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("no title column"), "{body}");
+
+        let (status, body) = import(&format!("title,content\nt,{}", "x".repeat(5000))).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(body.contains("4096 byte limit"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn unknown_api_paths_are_not_routed() {
+        let (status, _) = get("/api/notebook").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,150 @@
+use sqlx::mysql::MySqlPool;
+
+use crate::auth;
+
+// Owner of the sample notes, log in with these to see them
+pub const SEED_EMAIL: &str = "dev@example.com";
+pub const SEED_PASSWORD: &str = "password";
+const SEED_USER_ID: &str = "00000000-0000-4000-8000-000000000001";
+
+// Fixed ids and dates, so every run produces the same rows
+struct SampleNote {
+    id: &'static str,
+    title: &'static str,
+    content: &'static str,
+    is_published: bool,
+    created_at: &'static str,
+    tags: &'static [&'static str],
+}
+
+const SAMPLE_NOTES: [SampleNote; 5] = [
+    SampleNote {
+        id: "00000000-0000-4000-8000-000000000101",
+        title: "Welcome",
+        content: "This note was created by `seed`, run it again to get a fresh set.",
+        is_published: true,
+        created_at: "2024-03-01 09:00:00",
+        tags: &["getting-started"],
+    },
+    SampleNote {
+        id: "00000000-0000-4000-8000-000000000102",
+        title: "Shopping list",
+        content: "eggs, milk, coffee",
+        is_published: false,
+        created_at: "2024-03-02 17:30:00",
+        tags: &["personal", "todo"],
+    },
+    SampleNote {
+        id: "00000000-0000-4000-8000-000000000103",
+        title: "Reminder: dentist",
+        content: "Tuesday at 10:00",
+        is_published: false,
+        created_at: "2024-03-03 08:15:00",
+        tags: &["personal"],
+    },
+    SampleNote {
+        id: "00000000-0000-4000-8000-000000000104",
+        title: "Release checklist",
+        content: "Bump the version, run the migrations, tag the release.",
+        is_published: true,
+        created_at: "2024-03-04 14:00:00",
+        tags: &["work", "todo"],
+    },
+    SampleNote {
+        id: "00000000-0000-4000-8000-000000000105",
+        title: "Ideas",
+        content: "Dark mode\nExport to CSV\nShare a note by link",
+        is_published: false,
+        created_at: "2024-03-05 21:45:00",
+        tags: &[],
+    },
+];
+
+// Development only: wipes every note of every user and inserts
+// SAMPLE_NOTES for the SEED_EMAIL user, who is created when missing.
+// Everything happens in one transaction. Notes cached in Redis expire
+// after NOTE_CACHE_TTL_SECS. Returns the number of notes inserted.
+pub async fn seed_notes(db: &MySqlPool) -> Result<usize, Box<dyn std::error::Error>> {
+    let password_hash =
+        tokio::task::spawn_blocking(|| auth::hash_password(SEED_PASSWORD)).await??;
+
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        r#"INSERT INTO users (id, email, password_hash) VALUES (?, ?, ?)
+        ON DUPLICATE KEY UPDATE password_hash = VALUES(password_hash)"#,
+    )
+    .bind(SEED_USER_ID)
+    .bind(SEED_EMAIL)
+    .bind(&password_hash)
+    .execute(&mut *tx)
+    .await?;
+    // The email may already belong to a user registered by hand
+    let user_id: String = sqlx::query_scalar(r#"SELECT id FROM users WHERE email = ?"#)
+        .bind(SEED_EMAIL)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    // Tag links and history go with the notes through ON DELETE CASCADE
+    sqlx::query(r#"DELETE FROM notes"#)
+        .execute(&mut *tx)
+        .await?;
+    for note in &SAMPLE_NOTES {
+        sqlx::query(
+            r#"INSERT INTO notes (id, user_id, title, content, is_published, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(note.id)
+        .bind(&user_id)
+        .bind(note.title)
+        .bind(note.content)
+        .bind(note.is_published)
+        .bind(note.created_at)
+        .bind(note.created_at)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"INSERT INTO note_history (note_id, user_id, action) VALUES (?, ?, 'create')"#,
+        )
+        .bind(note.id)
+        .bind(&user_id)
+        .execute(&mut *tx)
+        .await?;
+        for tag in note.tags {
+            sqlx::query(
+                r#"INSERT INTO tags (name) VALUES (?) ON DUPLICATE KEY UPDATE name = name"#,
+            )
+            .bind(tag)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(
+                r#"INSERT INTO note_tags (note_id, tag_id) SELECT ?, id FROM tags WHERE name = ?"#,
+            )
+            .bind(note.id)
+            .bind(tag)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+
+    Ok(SAMPLE_NOTES.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test(migrations = "./migrations")]
+    #[ignore = "needs a MySQL server, run with DATABASE_URL set and --ignored"]
+    async fn seeding_twice_gives_the_same_notes(pool: MySqlPool) {
+        assert_eq!(seed_notes(&pool).await.unwrap(), SAMPLE_NOTES.len());
+        assert_eq!(seed_notes(&pool).await.unwrap(), SAMPLE_NOTES.len());
+
+        let ids: Vec<String> = sqlx::query_scalar(r#"SELECT id FROM notes ORDER BY id"#)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        let expected = SAMPLE_NOTES.iter().map(|note| note.id).collect::<Vec<_>>();
+        assert_eq!(ids, expected);
+    }
+}
@@ -0,0 +1,50 @@
+use std::path::{Path as FsPath, PathBuf};
+
+use axum::{
+    extract::{Request, State},
+    handler::Handler,
+    response::{IntoResponse, Response},
+    Router,
+};
+use tower::ServiceExt;
+use tower_http::services::{ServeDir, ServeFile};
+
+use crate::error::AppError;
+
+// GET /static/*, the files in STATIC_DIR. Only this prefix is served, so API
+// routes are never shadowed. Directories get their index.html, never a
+// listing.
+//
+// A missing path without an extension, say /static/notes/42, is a route of
+// the single page app and gets STATIC_DIR/index.html so the app can route
+// it. A missing file with an extension (/static/app.js) is a plain 404.
+pub fn static_files(dir: &FsPath) -> Router {
+    let spa_index = spa_fallback.with_state(dir.join("index.html"));
+    Router::new().nest_service("/static", ServeDir::new(dir).fallback(spa_index))
+}
+
+async fn spa_fallback(State(index): State<PathBuf>, req: Request) -> Response {
+    if !is_spa_route(req.uri().path()) {
+        return AppError::NotFound(format!("Static file {} not found", req.uri().path()))
+            .into_response();
+    }
+    // ServeFile can't fail, a missing index.html is an empty 404
+    ServeFile::new(index).oneshot(req).await.into_response()
+}
+
+fn is_spa_route(path: &str) -> bool {
+    FsPath::new(path).extension().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_paths_without_an_extension_are_app_routes() {
+        assert!(is_spa_route("/notes/42"));
+        assert!(is_spa_route("/"));
+        assert!(!is_spa_route("/app.js"));
+        assert!(!is_spa_route("/assets/logo.svg"));
+    }
+}
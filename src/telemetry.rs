@@ -0,0 +1,181 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+    routing::get,
+    Router,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sqlx::MySqlPool;
+use tracing::Span;
+use tracing_subscriber::EnvFilter;
+
+use crate::AppState;
+
+// Used when RUST_LOG is not set.
+const DEFAULT_LOG_FILTER: &str = "webserver_base=info,tower_http=info,sqlx::query=warn";
+
+// Install the global tracing subscriber. Verbosity follows RUST_LOG,
+// e.g. `RUST_LOG=debug` or `RUST_LOG=webserver_base=debug,sqlx=warn`.
+// LOG_FORMAT=json writes one JSON object per line for log aggregation,
+// anything else keeps the human readable format. It is read here rather
+// than in Config so that config errors are logged in the chosen format.
+pub fn init_tracing() {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match std::env::var("LOG_FORMAT") {
+        // Event fields sit at the top level, the request span (with its
+        // request_id) is kept under "span"
+        Ok(format) if format.eq_ignore_ascii_case("json") => {
+            builder.json().flatten_event(true).init()
+        }
+        _ => builder.init(),
+    }
+}
+
+// Root span for every request. The x-request-id header is set by
+// SetRequestIdLayer before this runs, so all events logged while handling
+// the request carry the same id.
+pub fn make_request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    )
+}
+
+// Middleware logging one access event per request. It runs inside the
+// request span, after SetRequestIdLayer, and repeats the request id as a
+// field so that JSON log lines can be filtered on it directly.
+pub async fn access_log(req: Request, next: Next) -> Response {
+    let method = req.method().clone();
+    let path = req.uri().path().to_owned();
+    let request_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned();
+    let started = Instant::now();
+
+    let response = next.run(req).await;
+
+    tracing::info!(
+        method = %method,
+        path = %path,
+        status = response.status().as_u16(),
+        latency_ms = started.elapsed().as_millis() as u64,
+        request_id = %request_id,
+        "request completed"
+    );
+    response
+}
+
+// Histogram buckets for request latency, in seconds.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// How often the recorder drains histogram samples when nothing scrapes it.
+const METRICS_UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+// Install the global Prometheus recorder. Must run inside the Tokio runtime
+// and before the first metric is recorded.
+pub fn install_metrics_recorder() -> PrometheusHandle {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full("http_request_duration_seconds".to_string()),
+            &LATENCY_BUCKETS,
+        )
+        .expect("latency buckets must not be empty")
+        .install_recorder()
+        .expect("failed to install Prometheus recorder");
+
+    let upkeep_handle = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(METRICS_UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            upkeep_handle.run_upkeep();
+        }
+    });
+
+    handle
+}
+
+// Middleware recording request counts, latency and in-flight requests.
+// Requests are labelled with the route template (e.g. /api/notes/:id) rather
+// than the raw path, so ids don't explode the number of series.
+pub async fn track_metrics(req: Request, next: Next) -> Response {
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched| matched.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".to_owned());
+    let method = req.method().to_string();
+    let labels = [("method", method), ("path", path)];
+
+    let in_flight = metrics::gauge!("http_requests_in_flight", &labels);
+    in_flight.increment(1.0);
+    let started = Instant::now();
+
+    let response = next.run(req).await;
+
+    in_flight.decrement(1.0);
+    // The status is only known once the response exists, so the in-flight
+    // gauge goes without it
+    let [method, path] = labels;
+    let labels = [
+        method,
+        path,
+        ("status", response.status().as_u16().to_string()),
+    ];
+    metrics::counter!("http_requests_total", &labels).increment(1);
+    metrics::histogram!("http_request_duration_seconds", &labels)
+        .record(started.elapsed().as_secs_f64());
+
+    response
+}
+
+// GET /metrics in Prometheus text format.
+pub fn metrics_router(app_state: Arc<AppState>) -> Router {
+    async fn get_metrics(State(state): State<Arc<AppState>>) -> String {
+        record_pool_metrics(&state);
+        state.metrics.render()
+    }
+
+    Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(app_state)
+}
+
+// Pool gauges are sampled at scrape time, they have no natural event to hook.
+// Without DATABASE_REPLICA_URL db_read is the primary pool, so the replica
+// is only reported when it is configured.
+fn record_pool_metrics(state: &AppState) {
+    record_pool(&state.db, "primary");
+    if state.config.database_replica_url.is_some() {
+        record_pool(&state.db_read, "replica");
+    }
+}
+
+fn record_pool(pool: &MySqlPool, name: &'static str) {
+    let size = pool.size() as f64;
+    let idle = pool.num_idle() as f64;
+    metrics::gauge!("db_pool_connections", "pool" => name, "state" => "idle").set(idle);
+    metrics::gauge!("db_pool_connections", "pool" => name, "state" => "active").set(size - idle);
+}
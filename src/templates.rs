@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use axum_template::{
+    engine::{Engine, HandlebarsError},
+    TemplateEngine,
+};
+use handlebars::{DirectorySourceOptions, Handlebars};
+use serde::Serialize;
+
+use crate::error::AppError;
+
+// Handlebars wrapped for axum_template, cheap to clone
+pub type AppEngine = Engine<Handlebars<'static>>;
+
+// Templates the handlers render, startup fails when one is missing rather
+// than the first request that needs it
+const REQUIRED_TEMPLATES: &[&str] = &["poem"];
+
+// Registers every `.hbs` file under TEMPLATES_DIR by its path without the
+// extension, `templates/poem.hbs` becomes `poem`. `{{ }}` HTML-escapes its
+// value. Debug builds re-read each template on render, so edits show up
+// without a restart.
+pub fn engine(dir: &Path) -> Result<AppEngine, Box<dyn std::error::Error>> {
+    let mut handlebars = Handlebars::new();
+    // A typo in a field name is an error instead of an empty string
+    handlebars.set_strict_mode(true);
+    // Has to be set before templates are registered to take effect
+    handlebars.set_dev_mode(cfg!(debug_assertions));
+    handlebars.register_templates_directory(dir, DirectorySourceOptions::default())?;
+
+    for name in REQUIRED_TEMPLATES {
+        if !handlebars.has_template(name) {
+            return Err(format!("template {}.hbs not found in {}", name, dir.display()).into());
+        }
+    }
+    Ok(Engine::from(handlebars))
+}
+
+// Renders to a string, so a failure becomes an AppError with the usual JSON
+// body instead of axum_template's plain text 500
+pub fn render<T: Serialize>(engine: &AppEngine, name: &str, data: &T) -> Result<String, AppError> {
+    engine
+        .render(name, data)
+        .map_err(|HandlebarsError::RenderError(err)| err.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poem_template_escapes_html() {
+        let poem = serde_json::json!({ "title": "<script>alert(1)</script>", "text": "a & b" });
+        let html = engine(Path::new("templates"))
+            .unwrap()
+            .render("poem", poem)
+            .unwrap();
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains("a &amp; b"));
+    }
+
+    #[test]
+    fn missing_required_templates_fail_startup() {
+        assert!(engine(Path::new("src")).is_err());
+    }
+
+    #[test]
+    fn render_failures_are_app_errors() {
+        let engine = engine(Path::new("templates")).unwrap();
+        // Strict mode makes the missing fields an error
+        let result = render(&engine, "poem", &serde_json::json!({}));
+        assert!(matches!(result, Err(AppError::Template(_))));
+    }
+}
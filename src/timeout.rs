@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::error::AppError;
+
+// Bounds the whole handler, DB and Redis calls included. When the deadline
+// passes the handler future is dropped, which cancels whatever it was
+// awaiting, and the client gets a 504. MySQL itself aborts SELECTs that
+// run longer, see max_execution_time in main.
+pub async fn request_timeout(
+    State(limit): State<Duration>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    tokio::time::timeout(limit, next.run(req))
+        .await
+        .map_err(|_| AppError::Timeout(limit.as_secs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::StatusCode, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app(limit: Duration) -> Router {
+        Router::new()
+            .route("/fast", get(|| async { "done" }))
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    "too late"
+                }),
+            )
+            .layer(middleware::from_fn_with_state(limit, request_timeout))
+    }
+
+    async fn send(path: &str) -> (StatusCode, Option<String>) {
+        let request = Request::builder().uri(path).body(Body::empty()).unwrap();
+        let response = app(Duration::from_millis(50))
+            .oneshot(request)
+            .await
+            .unwrap();
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .map(|value| value.to_str().unwrap().to_string());
+        (response.status(), content_type)
+    }
+
+    #[tokio::test]
+    async fn slow_handler_times_out_with_json_504() {
+        let (status, content_type) = send("/slow").await;
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(content_type.as_deref(), Some("application/json"));
+    }
+
+    #[tokio::test]
+    async fn fast_handler_is_unaffected() {
+        let (status, _) = send("/fast").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
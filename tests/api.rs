@@ -0,0 +1,240 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        Method, Request, StatusCode,
+    },
+    Router,
+};
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlPool;
+use tower::ServiceExt;
+
+// Each test gets its own database from #[sqlx::test], migrated and dropped
+// afterwards. Redis is shared, so the tests only rely on it for readiness and
+// the note cache, whose keys are per note.
+
+async fn send(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    token: Option<&str>,
+    json: Option<Value>,
+) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let body = match json {
+        Some(json) => {
+            request = request.header(CONTENT_TYPE, "application/json");
+            Body::from(json.to_string())
+        }
+        None => Body::empty(),
+    };
+    let response = app
+        .clone()
+        .oneshot(request.body(body).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    // 204s and plain text bodies come back as Null
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+// Registers the email and returns a bearer token for it
+async fn sign_up(app: &Router, email: &str) -> String {
+    let credentials = json!({ "email": email, "password": "correct horse" });
+    let (status, body) = send(
+        app,
+        Method::POST,
+        "/api/auth/register",
+        None,
+        Some(credentials.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+
+    let (status, body) = send(
+        app,
+        Method::POST,
+        "/api/auth/login",
+        None,
+        Some(credentials),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    body["token"].as_str().unwrap().to_string()
+}
+
+fn app(db: MySqlPool) -> Router {
+    common::app(common::config(), db)
+}
+
+#[sqlx::test(migrations = "./migrations")]
+#[ignore = "needs MySQL and Redis, run with DATABASE_URL and REDIS_URL set and --ignored"]
+async fn healthcheck_reports_both_dependencies(db: MySqlPool) {
+    let (status, body) = send(&app(db), Method::GET, "/healthcheck", None, None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["db"]["status"], "ok", "{body}");
+    assert_eq!(body["redis"]["status"], "ok", "{body}");
+}
+
+#[sqlx::test(migrations = "./migrations")]
+#[ignore = "needs MySQL and Redis, run with DATABASE_URL and REDIS_URL set and --ignored"]
+async fn notes_can_be_created_read_updated_and_deleted(db: MySqlPool) {
+    let app = app(db);
+    let token = sign_up(&app, "crud@example.com").await;
+    let token = Some(token.as_str());
+
+    let note = json!({ "title": "Groceries", "content": "eggs", "tags": ["home"] });
+    let (status, body) = send(&app, Method::POST, "/api/notes", token, Some(note)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["status"], "success");
+    let id = body["data"]["note"]["id"].as_str().unwrap().to_string();
+    let uri = format!("/api/notes/{id}");
+
+    let (status, body) = send(&app, Method::GET, &uri, token, None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["data"]["note"]["title"], "Groceries");
+    assert_eq!(body["data"]["note"]["tags"], json!(["home"]));
+
+    let edit = json!({ "content": "eggs, milk" });
+    let (status, body) = send(&app, Method::PATCH, &uri, token, Some(edit)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["data"]["note"]["title"], "Groceries");
+    assert_eq!(body["data"]["note"]["content"], "eggs, milk");
+    assert_eq!(body["data"]["note"]["tags"], json!(["home"]));
+
+    let (status, body) = send(&app, Method::GET, "/api/notes", token, None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["data"].as_array().unwrap().len(), 1, "{body}");
+
+    let (status, _) = send(&app, Method::DELETE, &uri, token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) = send(&app, Method::GET, &uri, token, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{body}");
+    assert!(body["error"].is_string(), "{body}");
+}
+
+#[sqlx::test(migrations = "./migrations")]
+#[ignore = "needs MySQL and Redis, run with DATABASE_URL and REDIS_URL set and --ignored"]
+async fn other_users_notes_are_not_found(db: MySqlPool) {
+    let app = app(db);
+    let owner = sign_up(&app, "owner@example.com").await;
+    let other = sign_up(&app, "other@example.com").await;
+
+    let note = json!({ "title": "Private", "content": "mine" });
+    let (_, body) = send(&app, Method::POST, "/api/notes", Some(&owner), Some(note)).await;
+    let uri = format!(
+        "/api/notes/{}",
+        body["data"]["note"]["id"].as_str().unwrap()
+    );
+
+    let (status, _) = send(&app, Method::GET, &uri, Some(&other), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send(&app, Method::DELETE, &uri, Some(&other), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send(&app, Method::GET, &uri, Some(&owner), None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+#[ignore = "needs MySQL and Redis, run with DATABASE_URL and REDIS_URL set and --ignored"]
+async fn duplicates_copy_the_content_and_tags(db: MySqlPool) {
+    let app = app(db);
+    let owner = sign_up(&app, "copy@example.com").await;
+    let other = sign_up(&app, "not-mine@example.com").await;
+
+    let note = json!({ "title": "Template", "content": "fill me in", "tags": ["b", "a"] });
+    let (_, body) = send(&app, Method::POST, "/api/notes", Some(&owner), Some(note)).await;
+    let source_id = body["data"]["note"]["id"].as_str().unwrap().to_string();
+    let uri = format!("/api/notes/{source_id}/duplicate");
+
+    let (status, body) = send(&app, Method::POST, &uri, Some(&owner), None).await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let copy = &body["data"]["note"];
+    assert_ne!(copy["id"], source_id.as_str());
+    assert_eq!(copy["title"], "Template (copy)");
+    assert_eq!(copy["content"], "fill me in");
+    assert_eq!(copy["tags"], json!(["a", "b"]));
+
+    // The copy's title is taken now
+    let (status, _) = send(&app, Method::POST, &uri, Some(&owner), None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = send(&app, Method::POST, &uri, Some(&other), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let missing = format!("/api/notes/{}/duplicate", uuid::Uuid::new_v4());
+    let (status, _) = send(&app, Method::POST, &missing, Some(&owner), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+#[ignore = "needs MySQL and Redis, run with DATABASE_URL and REDIS_URL set and --ignored"]
+async fn an_empty_patch_changes_nothing(db: MySqlPool) {
+    let app = app(db);
+    let token = sign_up(&app, "patch@example.com").await;
+    let token = Some(token.as_str());
+
+    let note = json!({ "title": "Unchanged", "content": "as it was", "tags": ["kept"] });
+    let (_, body) = send(&app, Method::POST, "/api/notes", token, Some(note)).await;
+    let created = body["data"]["note"].clone();
+    let uri = format!("/api/notes/{}", created["id"].as_str().unwrap());
+
+    let (status, body) = send(&app, Method::PATCH, &uri, token, Some(json!({}))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let patched = &body["data"]["note"];
+    for field in ["title", "content", "is_published", "updated_at", "tags"] {
+        assert_eq!(patched[field], created[field], "{field}");
+    }
+
+    // Only the create is in the history
+    let (_, body) = send(&app, Method::GET, &format!("{uri}/history"), token, None).await;
+    let history = body["data"]["history"].as_array().unwrap();
+    assert_eq!(history.len(), 1, "{body}");
+}
+
+#[sqlx::test(migrations = "./migrations")]
+#[ignore = "needs MySQL and Redis, run with DATABASE_URL and REDIS_URL set and --ignored"]
+async fn probes_pass_the_rate_limit(db: MySqlPool) {
+    let mut config = common::config();
+    config.rate_limit_per_minute = 1;
+    let app = common::app(config, db);
+
+    // Oneshot requests have no peer address, so every test shares one
+    // counter and only the second request is sure to be limited
+    send(&app, Method::GET, "/", None, None).await;
+    let (status, _) = send(&app, Method::GET, "/", None, None).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    for uri in ["/livez", "/healthcheck/live", "/readyz", "/metrics"] {
+        let (status, body) = send(&app, Method::GET, uri, None, None).await;
+        assert_eq!(status, StatusCode::OK, "{uri} {body}");
+    }
+}
+
+#[sqlx::test(migrations = "./migrations")]
+#[ignore = "needs MySQL and Redis, run with DATABASE_URL and REDIS_URL set and --ignored"]
+async fn search_pages_like_the_list(db: MySqlPool) {
+    let app = app(db);
+    let token = sign_up(&app, "search@example.com").await;
+    let token = Some(token.as_str());
+    for title in ["Plan A", "Plan B", "Other"] {
+        let note = json!({ "title": title, "content": "..." });
+        send(&app, Method::POST, "/api/notes", token, Some(note)).await;
+    }
+
+    let uri = "/api/notes/search?q=Plan&limit=1&page=2";
+    let (status, body) = send(&app, Method::GET, uri, token, None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["page"], 2);
+    assert_eq!(body["limit"], 1);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1, "{body}");
+
+    let (status, _) = send(&app, Method::GET, "/api/notes/search?q=%20", token, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
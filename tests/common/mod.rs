@@ -0,0 +1,71 @@
+// Shared by the files under tests/, each of which is its own crate
+
+use std::{sync::Arc, time::Duration};
+
+use axum::Router;
+use metrics_exporter_prometheus::PrometheusBuilder;
+use sqlx::mysql::MySqlPool;
+use webserver_base::{
+    build_app, config::Config, note_events_channel, numbers_channel, poems::PoemCache, templates,
+    AppState, NoteStats,
+};
+
+// Redis is REDIS_URL when set, a closed port otherwise
+pub fn config() -> Config {
+    let redis_url =
+        std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:1/".to_string());
+    Config {
+        database_url: "mysql://localhost/notes".to_string(),
+        database_replica_url: None,
+        db_max_connections: 1,
+        connect_retries: 0,
+        db_acquire_timeout: Duration::from_secs(1),
+        db_idle_timeout: Duration::from_secs(1),
+        db_max_lifetime: Duration::from_secs(1),
+        redis_url,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        jwt_secret: "secret".to_string(),
+        redis_max_connections: 2,
+        rate_limit_per_minute: 1000,
+        trust_proxy: false,
+        shutdown_timeout: Duration::from_secs(1),
+        note_cache_ttl: Duration::from_secs(1),
+        cors_allowed_origins: Some(Vec::new()),
+        cors_allow_credentials: false,
+        max_body_bytes: 1024,
+        max_import_bytes: 4096,
+        max_batch: 10,
+        request_timeout: Duration::from_secs(5),
+        slow_query_threshold: Duration::from_secs(1),
+        poems_dir: "./poems".into(),
+        poem_cache_ttl: Duration::from_secs(1),
+        templates_dir: "./templates".into(),
+        static_dir: "./static".into(),
+        random_seed: None,
+    }
+}
+
+// The whole app as main builds it, on the given database
+pub fn app(config: Config, db: MySqlPool) -> Router {
+    build_app(state(config, db))
+}
+
+// What main puts in AppState, for the background tasks
+pub fn state(config: Config, db: MySqlPool) -> Arc<AppState> {
+    let redis = deadpool_redis::Config::from_url(&config.redis_url)
+        .create_pool(Some(deadpool_redis::Runtime::Tokio1))
+        .unwrap();
+    Arc::new(AppState {
+        db: db.clone(),
+        db_read: db,
+        redis,
+        poems: PoemCache::new(config.poem_cache_ttl),
+        engine: templates::engine(&config.templates_dir).unwrap(),
+        config,
+        // Not installed globally, so every test can build its own app
+        metrics: PrometheusBuilder::new().build_recorder().handle(),
+        note_events: note_events_channel(),
+        note_stats: NoteStats::new(),
+        numbers: numbers_channel(),
+    })
+}
@@ -0,0 +1,462 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{
+        header::{
+            ACCEPT, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_REQUEST_METHOD, CONTENT_TYPE,
+            ORIGIN, VARY,
+        },
+        HeaderValue, Method, Request, StatusCode,
+    },
+    Router,
+};
+use sqlx::mysql::MySqlPoolOptions;
+use tokio::sync::watch;
+use tower::ServiceExt;
+use webserver_base::spawn_note_event_relay;
+
+// The MySQL pool is lazy and never used by these routes. Redis is only
+// reached by the numbers routes and the rate limiter, which lets requests
+// through when Redis is down, so by default it points at a closed port.
+fn app() -> Router {
+    let config = common::config();
+    let db = MySqlPoolOptions::new()
+        .connect_lazy(&config.database_url)
+        .unwrap();
+    common::app(config, db)
+}
+
+async fn send(app: &Router, method: Method, uri: &str, json: Option<&str>) -> (StatusCode, String) {
+    let mut request = Request::builder().method(method).uri(uri);
+    let body = match json {
+        Some(json) => {
+            request = request.header(CONTENT_TYPE, "application/json");
+            Body::from(json.to_string())
+        }
+        None => Body::empty(),
+    };
+    let response = app
+        .clone()
+        .oneshot(request.body(body).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+async fn get(uri: &str) -> (StatusCode, String) {
+    send(&app(), Method::GET, uri, None).await
+}
+
+#[tokio::test]
+async fn greet_uses_the_path_name() {
+    assert_eq!(
+        get("/greet/Ferris").await,
+        (StatusCode::OK, "Hello, Ferris!".to_string())
+    );
+}
+
+#[tokio::test]
+async fn greet_query_falls_back_to_defaults() {
+    assert_eq!(
+        get("/greet").await,
+        (StatusCode::OK, "Hello, World!".to_string())
+    );
+    assert_eq!(
+        get("/greet?salutation=Hi&name=Ann").await,
+        (StatusCode::OK, "Hi, Ann!".to_string())
+    );
+}
+
+// Greetings are recorded in the background, so the history is polled. The
+// other greet tests add to it meanwhile, so ours needn't be the newest.
+#[tokio::test]
+#[ignore = "needs a Redis server, run with REDIS_URL set and --ignored"]
+async fn greetings_are_recorded() {
+    let app = app();
+    let name = format!("history-{}", std::process::id());
+    let uri = format!("/greet?salutation=Hi&name={name}");
+    let (status, _) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    for _ in 0..50 {
+        let (status, body) = send(&app, Method::GET, "/greet/history", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let history: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+        assert!(history.len() <= 100);
+        if let Some(greeting) = history.iter().find(|greeting| greeting["name"] == *name) {
+            assert_eq!(greeting["salutation"], "Hi");
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("greeting never showed up in the history");
+}
+
+async fn greet_accepting(accept: &str) -> (String, String) {
+    let response = app()
+        .oneshot(
+            Request::get("/greet?name=Ann")
+                .header("accept", accept)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let content_type = response.headers()[CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (content_type, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn greet_answers_json_when_the_client_prefers_it() {
+    for accept in ["application/json", "text/plain;q=0.5, application/json"] {
+        let (content_type, body) = greet_accepting(accept).await;
+        assert_eq!(content_type, "application/json", "{accept}");
+        assert_eq!(body, r#"{"message":"Hello, Ann!"}"#, "{accept}");
+    }
+    for accept in ["*/*", "text/html", "text/plain, application/json;q=0.9"] {
+        let (content_type, body) = greet_accepting(accept).await;
+        assert!(content_type.starts_with("text/plain"), "{accept}");
+        assert_eq!(body, "Hello, Ann!", "{accept}");
+    }
+}
+
+#[tokio::test]
+async fn strict_greet_rejects_unknown_parameters() {
+    assert_eq!(
+        get("/greet/strict?salutation=Hi&name=Ann").await,
+        (StatusCode::OK, "Hi, Ann!".to_string())
+    );
+    assert_eq!(
+        get("/greet/strict").await,
+        (StatusCode::OK, "Hello, World!".to_string())
+    );
+
+    let (status, body) = get("/greet/strict?nam=Bob&salutation=Hi&x=1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        body["error"],
+        "Unknown query parameters: nam, x, expected salutation or name"
+    );
+
+    // The lenient route still ignores them
+    assert_eq!(
+        get("/greet?nam=Bob").await,
+        (StatusCode::OK, "Hello, World!".to_string())
+    );
+}
+
+#[tokio::test]
+async fn greet_accepts_a_json_body() {
+    let response = send(
+        &app(),
+        Method::POST,
+        "/greet",
+        Some(r#"{"salutation":"Howdy","name":"Bob"}"#),
+    )
+    .await;
+    assert_eq!(response, (StatusCode::OK, "Howdy, Bob!".to_string()));
+}
+
+// The set is shared, so the test starts by removing its number
+#[tokio::test]
+#[ignore = "needs a Redis server, run with REDIS_URL set and --ignored"]
+async fn lookup_finds_registered_numbers_only() {
+    let app = app();
+    let uri = "/lookup/1000001";
+    let not_found = serde_json::json!({ "number": 1000001, "found": false });
+
+    let (status, _) = send(&app, Method::DELETE, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(&app, Method::GET, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body, not_found);
+
+    let (status, _) = send(&app, Method::POST, uri, None).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = send(&app, Method::POST, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(&app, Method::GET, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({ "number": 1000001, "found": true })
+    );
+
+    let (status, body) = send(&app, Method::DELETE, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body, not_found);
+    let (_, body) = send(&app, Method::GET, uri, None).await;
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body, not_found);
+}
+
+#[tokio::test]
+async fn unknown_paths_get_a_json_404() {
+    let (status, body) = get("/does/not/exist").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({ "error": "not found", "method": "GET", "path": "/does/not/exist" })
+    );
+}
+
+#[tokio::test]
+async fn the_poem_page_is_still_html() {
+    let response = app()
+        .oneshot(Request::get("/poem").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let content_type = response.headers()[CONTENT_TYPE].to_str().unwrap();
+    assert!(content_type.starts_with("text/html"), "{content_type}");
+}
+
+#[tokio::test]
+async fn poems_are_json_when_the_client_asks_for_it() {
+    let response = app()
+        .oneshot(
+            Request::get("/poem")
+                .header(ACCEPT, "text/html;q=0.9, application/json")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    assert_eq!(response.headers()[VARY], "accept");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let poem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(poem["title"].is_string(), "{poem}");
+    assert!(poem["text"].is_string(), "{poem}");
+}
+
+#[tokio::test]
+async fn static_files_fall_back_to_the_app_page() {
+    for uri in ["/static/", "/static/index.html", "/static/notes/42"] {
+        let (status, body) = get(uri).await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+        assert!(body.contains("<title>Notes admin</title>"), "{uri}: {body}");
+    }
+
+    for uri in ["/static/missing.js", "/static/../Cargo.toml"] {
+        let (status, body) = get(uri).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{uri}");
+        assert!(!body.contains("[package]"), "{uri}: {body}");
+    }
+}
+
+// PUT /numbers is the only PUT route, browsers preflight it like the others
+#[tokio::test]
+async fn preflights_allow_every_method_the_routes_use() {
+    let mut config = common::config();
+    config.cors_allowed_origins = Some(vec![HeaderValue::from_static("https://app.example.com")]);
+    let db = MySqlPoolOptions::new()
+        .connect_lazy(&config.database_url)
+        .unwrap();
+    let request = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/numbers")
+        .header(ORIGIN, "https://app.example.com")
+        .header(ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+        .body(Body::empty())
+        .unwrap();
+    let response = common::app(config, db).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let allowed = response.headers()[ACCESS_CONTROL_ALLOW_METHODS]
+        .to_str()
+        .unwrap();
+    for method in ["GET", "POST", "PUT", "PATCH", "DELETE"] {
+        assert!(allowed.contains(method), "{allowed}");
+    }
+}
+
+#[tokio::test]
+async fn wrong_methods_list_the_allowed_ones() {
+    let app = app();
+    let response = app
+        .oneshot(Request::post("/random").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers()["allow"], "GET,HEAD");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "error": "Method not allowed, the Allow header lists the accepted methods"
+        })
+    );
+}
+
+#[tokio::test]
+async fn responses_carry_a_request_id() {
+    let response = app()
+        .oneshot(Request::get("/ping").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let request_id = response.headers().get("x-request-id").unwrap();
+    assert!(!request_id.is_empty());
+
+    // A client supplied id is kept, so callers can correlate their own logs
+    let response = app()
+        .oneshot(
+            Request::get("/ping")
+                .header("x-request-id", "abc-123")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-request-id"], "abc-123");
+}
+
+#[tokio::test]
+async fn error_responses_carry_the_request_id() {
+    for uri in ["/api/notes", "/does/not/exist", "/lookup/three"] {
+        let response = app()
+            .oneshot(
+                Request::get(uri)
+                    .header("x-request-id", "abc-123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(response.status().is_client_error(), "{uri}");
+        assert_eq!(response.headers()["x-request-id"], "abc-123", "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn random_is_reproducible_with_a_seed() {
+    let app = app();
+    let first = send(&app, Method::GET, "/random?seed=7", None).await;
+    for _ in 0..5 {
+        assert_eq!(send(&app, Method::GET, "/random?seed=7", None).await, first);
+    }
+}
+
+// The seeds documented on return_something_random
+#[tokio::test]
+async fn each_outcome_has_a_known_seed() {
+    assert_eq!(
+        get("/random?seed=2").await,
+        (StatusCode::OK, "Hello, World!".to_string())
+    );
+    assert_eq!(get("/random?seed=4").await.0, StatusCode::NOT_IMPLEMENTED);
+    assert_eq!(
+        get("/random?seed=0").await,
+        (StatusCode::OK, "42".to_string())
+    );
+}
+
+#[tokio::test]
+async fn random_seed_applies_when_the_request_has_none() {
+    let mut config = common::config();
+    config.random_seed = Some(4);
+    let db = MySqlPoolOptions::new()
+        .connect_lazy(&config.database_url)
+        .unwrap();
+    let app = common::app(config, db);
+    for _ in 0..5 {
+        let (status, _) = send(&app, Method::GET, "/random", None).await;
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    }
+    let (status, _) = send(&app, Method::GET, "/random?seed=2", None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn random_follows_the_weights() {
+    assert_eq!(
+        get("/random?weights=0,0,1").await,
+        (StatusCode::OK, "42".to_string())
+    );
+    assert_eq!(
+        get("/random?weights=0,%201,%200&seed=3").await.0,
+        StatusCode::NOT_IMPLEMENTED
+    );
+    for weights in ["1,2", "1,2,x", "0,0,0", "-1,1,1"] {
+        let (status, _) = get(&format!("/random?weights={weights}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{weights}");
+    }
+}
+
+#[tokio::test]
+async fn lookup_rejects_non_numbers() {
+    let (status, _) = get("/lookup/three").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+// The numbers live in a shared Redis list, so this clears it first
+#[tokio::test]
+#[ignore = "needs a Redis server, run with REDIS_URL set and --ignored"]
+async fn posted_numbers_are_listed() {
+    let app = app();
+    let (status, _) = send(&app, Method::PUT, "/numbers", Some("[]")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(&app, Method::POST, "/numbers", Some("7")).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "[7]"));
+
+    let (status, body) = send(&app, Method::GET, "/numbers", None).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "[7]"));
+}
+
+#[tokio::test]
+async fn redis_routes_report_outages_as_503() {
+    if std::env::var_os("REDIS_URL").is_some() {
+        return;
+    }
+    let (status, _) = get("/numbers").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let (status, _) = get("/lookup/3").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+// Without Redis the relay is waiting out its backoff, with it subscribed.
+// Either way shutdown ends it.
+#[tokio::test]
+async fn the_note_event_relay_stops_on_shutdown() {
+    let config = common::config();
+    let db = MySqlPoolOptions::new()
+        .connect_lazy(&config.database_url)
+        .unwrap();
+    let (shutdown, shutdown_receiver) = watch::channel(false);
+    let relay = spawn_note_event_relay(common::state(config, db), shutdown_receiver);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    shutdown.send(true).unwrap();
+    tokio::time::timeout(Duration::from_secs(1), relay)
+        .await
+        .expect("relay kept running after shutdown")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn every_notes_read_is_in_the_openapi_spec() {
+    let (status, body) = get("/api-docs/openapi.json").await;
+    assert_eq!(status, StatusCode::OK);
+    let spec: serde_json::Value = serde_json::from_str(&body).unwrap();
+    for path in ["/api/notes", "/api/notes/search", "/api/notes/{id}"] {
+        assert!(spec["paths"][path]["get"].is_object(), "{path}");
+    }
+}